@@ -0,0 +1,254 @@
+use crate::util::*;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Ident, Index, LitInt, LitStr,
+    Member, Result, Type,
+};
+
+/// The shape of a struct as a prolog compound term.
+struct CompoundDef {
+    functor_name: String,
+    // in order of argument position
+    args: Vec<(Member, Type)>,
+}
+
+impl CompoundDef {
+    fn from_input(input: &DeriveInput) -> Result<Self> {
+        let mut functor_name = None;
+        for attr in input.attrs.iter() {
+            if attr.path.is_ident("functor") {
+                functor_name = Some(attr.parse_args::<LitStr>()?.value());
+            }
+        }
+        let functor_name = functor_name.unwrap_or_else(|| snake_case(&input.ident.to_string()));
+
+        let fields = match &input.data {
+            Data::Struct(data) => &data.fields,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "term conversions can only be derived for structs",
+                ))
+            }
+        };
+
+        let mut args = Vec::with_capacity(fields.len());
+        let mut positions = Vec::with_capacity(fields.len());
+        for (index, field) in fields.iter().enumerate() {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(Index::from(index)),
+            };
+
+            let mut position = None;
+            for attr in field.attrs.iter() {
+                if attr.path.is_ident("arg") {
+                    let lit = attr.parse_args::<LitInt>()?;
+                    let value = lit.base10_parse::<usize>()?;
+                    if value == 0 || value > fields.len() {
+                        return Err(Error::new_spanned(
+                            lit,
+                            format!("argument position should be between 1 and {}", fields.len()),
+                        ));
+                    }
+                    position = Some(value);
+                }
+            }
+            positions.push(position);
+            args.push((member, field.ty.clone()));
+        }
+
+        if positions.iter().any(|p| p.is_some()) {
+            if positions.iter().any(|p| p.is_none()) {
+                return Err(Error::new_spanned(
+                    fields,
+                    "either all fields or no fields should have an #[arg(..)] attribute",
+                ));
+            }
+
+            let mut ordered: Vec<Option<(Member, Type)>> = vec![None; args.len()];
+            for (arg, position) in args.into_iter().zip(positions) {
+                let slot = &mut ordered[position.unwrap() - 1];
+                if slot.is_some() {
+                    return Err(Error::new_spanned(
+                        fields,
+                        format!(
+                            "argument position {} is used more than once",
+                            position.unwrap()
+                        ),
+                    ));
+                }
+                *slot = Some(arg);
+            }
+            args = ordered.into_iter().map(|arg| arg.unwrap()).collect();
+        }
+
+        if args.len() > u16::MAX as usize {
+            return Err(Error::new_spanned(
+                fields,
+                "too many fields for a prolog functor",
+            ));
+        }
+
+        Ok(Self { functor_name, args })
+    }
+
+    fn functor_tokens(&self) -> TokenStream {
+        let crt = crate_token();
+        let name = LitStr::new(&self.functor_name, Span::call_site());
+        let arity = self.args.len() as u16;
+
+        quote! {#crt::functor::Functor::new(#name, #arity)}
+    }
+}
+
+/// Turn a rust type name like `MyPoint` into a prolog name like `my_point`.
+fn snake_case(name: &str) -> String {
+    let mut result = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                result.push('_');
+            }
+            result.extend(c.to_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+pub fn derive_term_getable_macro(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let def = match CompoundDef::from_input(&input) {
+        Ok(def) => def,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let crt = crate_token();
+    let item_name = &input.ident;
+    let type_name = LitStr::new(&item_name.to_string(), Span::call_site());
+    let functor = def.functor_tokens();
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for (_, ty) in def.args.iter() {
+        where_clause
+            .predicates
+            .push(parse_quote! {#ty: #crt::term::TermGetable});
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let field_gets = def.args.iter().enumerate().map(|(i, (member, ty))| {
+        let index = i + 1;
+        let var = binding(member);
+        // an exception raised while getting a field stays pending,
+        // so that Term::get reports it after this returns None.
+        quote! {
+            let #var = term.get_arg::<#ty>(#index).ok()?;
+        }
+    });
+    let construct = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(_) => {
+                let fields = def.args.iter().map(|(member, _)| {
+                    let var = binding(member);
+                    quote! {#member: #var}
+                });
+                quote! {Self { #(#fields),* }}
+            }
+            Fields::Unnamed(fields) => {
+                let vars = (0..fields.unnamed.len()).map(tuple_binding);
+                quote! {Self( #(#vars),* )}
+            }
+            Fields::Unit => quote! {Self},
+        },
+        _ => unreachable!(),
+    };
+
+    let result = quote! {
+        // unsafe justification: like with the term_getable! macro,
+        // the generated body asserts that term handling is possible
+        // before touching the term.
+        unsafe impl #impl_generics #crt::term::TermGetable for #item_name #ty_generics #where_clause {
+            fn get(term: &#crt::term::Term) -> Option<Self> {
+                term.assert_term_handling_possible();
+
+                if term.get::<#crt::functor::Functor>().ok()? != #functor {
+                    return None;
+                }
+                #(#field_gets)*
+
+                Some(#construct)
+            }
+
+            fn name() -> &'static str {
+                #type_name
+            }
+        }
+    };
+
+    result.into()
+}
+
+pub fn derive_unifiable_macro(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let def = match CompoundDef::from_input(&input) {
+        Ok(def) => def,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let crt = crate_token();
+    let item_name = &input.ident;
+    let functor = def.functor_tokens();
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for (_, ty) in def.args.iter() {
+        where_clause
+            .predicates
+            .push(parse_quote! {#ty: #crt::term::Unifiable});
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let arg_unifications = def.args.iter().enumerate().map(|(i, (member, _))| {
+        let index = i + 1;
+        quote! {
+            && term.unify_arg(#index, &self.#member).is_ok()
+        }
+    });
+
+    let result = quote! {
+        // unsafe justification: like with the unifiable! macro, the
+        // generated body asserts that term handling is possible
+        // before touching the term.
+        unsafe impl #impl_generics #crt::term::Unifiable for #item_name #ty_generics #where_clause {
+            fn unify(&self, term: &#crt::term::Term) -> bool {
+                term.assert_term_handling_possible();
+
+                term.unify(#functor).is_ok()
+                    #(#arg_unifications)*
+            }
+        }
+    };
+
+    result.into()
+}
+
+/// The name of the variable a field is retrieved into.
+///
+/// This is prefixed so that fields can't shadow the term that is
+/// being retrieved from.
+fn binding(member: &Member) -> Ident {
+    match member {
+        Member::Named(ident) => Ident::new(&format!("__field_{}", ident), Span::call_site()),
+        Member::Unnamed(index) => tuple_binding(index.index as usize),
+    }
+}
+
+fn tuple_binding(index: usize) -> Ident {
+    Ident::new(&format!("__field_{}", index), Span::call_site())
+}
@@ -6,6 +6,7 @@ mod util;
 
 mod atom;
 mod blob;
+mod derive;
 mod functor;
 mod pred;
 mod predicate;
@@ -319,3 +320,51 @@ pub fn atom(item: TokenStream) -> TokenStream {
 pub fn functor(item: TokenStream) -> TokenStream {
     functor::functor_macro(item)
 }
+
+/// Derive `TermGetable` for a struct, retrieving it from a compound term.
+///
+/// A struct with `n` fields is retrieved from a compound term of
+/// arity `n`, where each argument is retrieved as the type of the
+/// corresponding field. By default, the name of the functor is the
+/// name of the struct in snake case, and the arguments are in the
+/// order the fields were declared in. A `#[functor("name")]`
+/// attribute on the struct overrides the name, and `#[arg(position)]`
+/// attributes on all fields override the order. Positions start at 1.
+///
+/// For terms of a different shape, or arguments that can't be
+/// retrieved as the type of their field, getting fails.
+///
+/// Example:
+/// ```ignore
+/// #[derive(TermGetable, Unifiable)]
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// #[derive(TermGetable, Unifiable)]
+/// #[functor("edge")]
+/// struct Connection {
+///     #[arg(2)]
+///     to: Atom,
+///     #[arg(1)]
+///     from: Atom,
+/// }
+///
+/// let point: Point = term!{context: point(1.0, 2.0)}?.get()?;
+/// ```
+#[proc_macro_derive(TermGetable, attributes(functor, arg))]
+pub fn derive_term_getable(item: TokenStream) -> TokenStream {
+    derive::derive_term_getable_macro(item)
+}
+
+/// Derive `Unifiable` for a struct, unifying it with a compound term.
+///
+/// This uses the same functor name and argument order as
+/// [TermGetable](macro@TermGetable), and supports the same
+/// attributes. Unification succeeds if the term unifies with the
+/// functor, and each argument unifies with the corresponding field.
+#[proc_macro_derive(Unifiable, attributes(functor, arg))]
+pub fn derive_unifiable(item: TokenStream) -> TokenStream {
+    derive::derive_unifiable_macro(item)
+}
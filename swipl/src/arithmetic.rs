@@ -0,0 +1,561 @@
+//! Arithmetic evaluation, and the numbers it produces.
+//!
+//! Depending on the expression and on prolog flags like
+//! `prefer_rationals`, arithmetic in SWI-Prolog may produce small
+//! integers, big integers, rationals or floats. The [Number] type
+//! represents all of these without any loss of precision.
+//!
+//! With the `num-bigint` feature enabled, integers of any size can
+//! also be retrieved and unified as a `num_bigint::BigInt`.
+use std::ffi::CString;
+use std::os::raw::c_char;
+
+use crate::context::*;
+use crate::fli;
+use crate::result::*;
+use crate::term::*;
+use crate::{functor, pred, term_getable, unifiable};
+
+/// A number as it is represented in prolog.
+///
+/// Big integers and rationals are kept in decimal notation, so that
+/// no precision is lost. It is up to the user to convert these into
+/// a suitable rust type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Number {
+    /// An integer that fits in an `i64`.
+    Integer(i64),
+    /// An integer that does not fit in an `i64`, in decimal notation.
+    BigInteger(String),
+    /// A rational number which is not an integer, as its numerator
+    /// and denominator in decimal notation. The denominator is
+    /// always positive.
+    Rational(String, String),
+    /// A floating point number.
+    Float(f64),
+}
+
+fn number_text(term: &Term, flags: u32) -> Option<String> {
+    let mut len: usize = 0;
+    let mut s: *mut c_char = std::ptr::null_mut();
+    let flags = flags | fli::BUF_DISCARDABLE | fli::REP_UTF8;
+    let result = unsafe { fli::PL_get_nchars(term.term_ptr(), &mut len, &mut s, flags) };
+    if result == 0 {
+        return None;
+    }
+
+    let slice = unsafe { std::slice::from_raw_parts(s as *const u8, len) };
+    std::str::from_utf8(slice).ok().map(|s| s.to_string())
+}
+
+term_getable! {
+    (Number, "number", term) => {
+        match term.term_type() {
+            TermType::Integer => {
+                let mut out = 0;
+                if unsafe { fli::PL_get_int64(term.term_ptr(), &mut out) } != 0 {
+                    Some(Number::Integer(out))
+                }
+                else {
+                    number_text(term, fli::CVT_INTEGER).map(Number::BigInteger)
+                }
+            },
+            TermType::Rational => {
+                let text = number_text(term, fli::CVT_RATIONAL)?;
+                let (numerator, denominator) = text.split_once(['r', '/'])?;
+
+                Some(Number::Rational(numerator.to_string(), denominator.to_string()))
+            },
+            TermType::Float => {
+                let mut out = 0.0;
+                if unsafe { fli::PL_get_float(term.term_ptr(), &mut out) } != 0 {
+                    Some(Number::Float(out))
+                }
+                else {
+                    None
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+/// Unify the term with the number written in the given text.
+///
+/// The text is only passed to the prolog reader if it is a plain
+/// integer or rational, so that no other kind of term can sneak in.
+fn unify_number_text(term: &Term, text: &str) -> bool {
+    fn is_integer(s: &str) -> bool {
+        let digits = s.strip_prefix('-').unwrap_or(s);
+        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+    }
+    let valid = match text.split_once('r') {
+        Some((numerator, denominator)) => {
+            is_integer(numerator)
+                && !denominator.is_empty()
+                && denominator.bytes().all(|b| b.is_ascii_digit())
+        }
+        None => is_integer(text),
+    };
+    if !valid {
+        return false;
+    }
+
+    let text = CString::new(text).unwrap();
+    unsafe {
+        let number = fli::PL_new_term_ref();
+        let result = fli::PL_chars_to_term(text.as_ptr(), number) != 0
+            && fli::PL_unify(term.term_ptr(), number) != 0;
+        fli::PL_reset_term_refs(number);
+
+        result
+    }
+}
+
+// Big integers and rationals are unified through their text, which
+// fails if that text is not a valid number.
+unifiable! {
+    (self: Number, term) => {
+        match self {
+            Number::Integer(i) => term.unify(*i).is_ok(),
+            Number::Float(f) => term.unify(*f).is_ok(),
+            Number::BigInteger(digits) => unify_number_text(term, digits),
+            Number::Rational(numerator, denominator) => {
+                unify_number_text(term, &format!("{}r{}", numerator, denominator))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+mod decimal {
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    fn gcd(mut a: i128, mut b: i128) -> i128 {
+        while b != 0 {
+            let r = a % b;
+            a = b;
+            b = r;
+        }
+
+        a.abs()
+    }
+
+    // Decimals are unified as exact rationals, or as integers when
+    // they have no fractional part.
+    unifiable! {
+        (self: Decimal, term) => {
+            let mut numerator = self.mantissa();
+            let mut denominator = 10_i128.pow(self.scale());
+            let divisor = gcd(numerator, denominator);
+            if divisor > 1 {
+                numerator /= divisor;
+                denominator /= divisor;
+            }
+
+            let text = if denominator == 1 {
+                format!("{}", numerator)
+            } else {
+                format!("{}r{}", numerator, denominator)
+            };
+
+            unify_number_text(term, &text)
+        }
+    }
+
+    // Integers and rationals are retrieved as a decimal if they can
+    // be represented exactly. Floats are never retrieved, as they are
+    // not exact to begin with.
+    term_getable! {
+        (Decimal, "decimal", term) => {
+            let (numerator, denominator) = match <Number as TermGetable>::get(term)? {
+                Number::Integer(i) => (i as i128, 1),
+                Number::BigInteger(digits) => (digits.parse().ok()?, 1),
+                Number::Rational(numerator, denominator) => {
+                    (numerator.parse().ok()?, denominator.parse::<i128>().ok()?)
+                }
+                Number::Float(_) => return None,
+            };
+
+            // find the smallest power of ten that is a multiple of the denominator
+            let mut scale = 0;
+            let mut power: i128 = 1;
+            while power % denominator != 0 {
+                scale += 1;
+                if scale > 28 {
+                    return None;
+                }
+                power *= 10;
+            }
+
+            let mantissa = numerator.checked_mul(power / denominator)?;
+            Decimal::try_from_i128_with_scale(mantissa, scale).ok()
+        }
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+mod bigint {
+    use num_bigint::BigInt;
+
+    use super::*;
+
+    unifiable! {
+        (self: BigInt, term) => {
+            unify_number_text(term, &self.to_string())
+        }
+    }
+
+    // Only integers are retrieved. Rationals and floats are not,
+    // even if they happen to have an integral value.
+    term_getable! {
+        (BigInt, "big integer", term) => {
+            match <Number as TermGetable>::get(term)? {
+                Number::Integer(i) => Some(BigInt::from(i)),
+                Number::BigInteger(digits) => digits.parse().ok(),
+                _ => None,
+            }
+        },
+        matches_kind(term) => {
+            term.is_integer()
+        }
+    }
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Evaluate an arithmetic expression using `is/2`.
+    ///
+    /// The result is returned as whatever kind of number prolog
+    /// produced, without any conversion. Errors in the expression,
+    /// such as unbound variables or division by zero, are raised as
+    /// exceptions.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let expr = context.term_from_string("1 rdiv 3 + 1")?;
+    /// assert_eq!(
+    ///     Number::Rational("4".to_string(), "3".to_string()),
+    ///     context.eval(&expr)?
+    /// );
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn eval(&self, expr: &Term) -> PrologResult<Number> {
+        let frame = self.open_frame();
+        let result = frame.new_term_ref();
+        frame.call_once(pred!(is / 2), [&result, expr])?;
+        let number = result.get::<Number>();
+        frame.discard();
+
+        number
+    }
+
+    /// Relate two integers through `succ/2`, where `y` is `x + 1`.
+    ///
+    /// Either of the two may be left out, and the missing one is
+    /// calculated and returned. If both are given, this checks that
+    /// they are related and returns `y`. This fails if `x` would
+    /// have to be negative, such as when `y` is 0. Leaving both out
+    /// raises an instantiation error.
+    pub fn succ(&self, x: Option<i64>, y: Option<i64>) -> PrologResult<i64> {
+        let frame = self.open_frame();
+        let [x_term, y_term] = frame.new_term_refs();
+        if let Some(x) = x {
+            x_term.unify(x)?;
+        }
+        if let Some(y) = y {
+            y_term.unify(y)?;
+        }
+
+        frame.call_once(pred!(succ / 2), [&x_term, &y_term])?;
+        let result = match x {
+            None => x_term.get(),
+            Some(_) => y_term.get(),
+        };
+        frame.discard();
+
+        result
+    }
+
+    /// Relate three integers through `plus/3`, where `z` is `x + y`.
+    ///
+    /// Any one of the three may be left out, and the missing one is
+    /// calculated and returned. If all three are given, this checks
+    /// that they are related and returns `z`. Leaving out more than
+    /// one raises an instantiation error.
+    pub fn plus(&self, x: Option<i64>, y: Option<i64>, z: Option<i64>) -> PrologResult<i64> {
+        let frame = self.open_frame();
+        let [x_term, y_term, z_term] = frame.new_term_refs();
+        for (term, value) in [(&x_term, x), (&y_term, y), (&z_term, z)] {
+            if let Some(value) = value {
+                term.unify(value)?;
+            }
+        }
+
+        frame.call_once(pred!(plus / 3), [&x_term, &y_term, &z_term])?;
+        let result = match (x, y) {
+            (None, _) => x_term.get(),
+            (_, None) => y_term.get(),
+            _ => z_term.get(),
+        };
+        frame.discard();
+
+        result
+    }
+
+    /// Seed the random number generator of the current thread using `set_random/1`.
+    ///
+    /// Seeding with the same value makes prolog's random predicates,
+    /// such as `random/1` and `random_between/3`, produce the same
+    /// sequence of numbers again. This is useful for reproducing runs
+    /// of code that relies on randomness, like in tests.
+    pub fn set_random_seed(&self, seed: u64) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let option = frame.new_term_ref();
+        option.unify(functor!("seed/1"))?;
+        option.unify_arg(1, seed)?;
+        frame.call_once(pred!(set_random / 1), [&option])?;
+        frame.discard();
+
+        Ok(())
+    }
+
+    /// Generate a random integer between `low` and `high`, inclusive, using `random_between/3`.
+    ///
+    /// This fails if `high` is smaller than `low`.
+    pub fn random_between(&self, low: i64, high: i64) -> PrologResult<i64> {
+        let frame = self.open_frame();
+        let [low_term, high_term, result] = frame.new_term_refs();
+        low_term.unify(low)?;
+        high_term.unify(high)?;
+        frame.call_once(pred!(random_between / 3), [&low_term, &high_term, &result])?;
+        let result = result.get();
+        frame.discard();
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::*;
+
+    fn eval_str<C: QueryableContextType>(context: &Context<C>, expr: &str) -> PrologResult<Number> {
+        let expr = context.term_from_string(expr)?;
+        context.eval(&expr)
+    }
+
+    #[test]
+    fn eval_to_each_kind_of_number() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert_eq!(Number::Integer(3), eval_str(&context, "1+2")?);
+        assert_eq!(Number::Integer(-7), eval_str(&context, "3-10")?);
+        assert_eq!(
+            Number::BigInteger("1267650600228229401496703205376".to_string()),
+            eval_str(&context, "2**100")?
+        );
+        assert_eq!(
+            Number::Rational("-1".to_string(), "3".to_string()),
+            eval_str(&context, "-1 rdiv 3")?
+        );
+        assert_eq!(Number::Integer(1), eval_str(&context, "1 rdiv 3 * 3")?);
+        assert_eq!(Number::Float(0.5), eval_str(&context, "1 rdiv 4 + 0.25")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn eval_raises_arithmetic_errors() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert!(eval_str(&context, "1 + X").unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn unify_numbers() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let numbers = [
+            Number::Integer(-42),
+            Number::BigInteger("-1267650600228229401496703205376".to_string()),
+            Number::Rational("2".to_string(), "3".to_string()),
+            Number::Float(0.25),
+        ];
+        for number in numbers.iter() {
+            let term = context.new_term_ref();
+            term.unify(number)?;
+            assert_eq!(*number, term.get::<Number>()?);
+        }
+
+        let term = context.new_term_ref();
+        assert!(term
+            .unify(Number::BigInteger("12 + 3".to_string()))
+            .unwrap_err()
+            .is_failure());
+        assert!(term.is_var());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn unify_and_get_big_integers() -> PrologResult<()> {
+        use crate::functor::*;
+        use num_bigint::BigInt;
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let big: BigInt = "1267650600228229401496703205376".parse().unwrap();
+        let term = context.new_term_ref();
+        term.unify(&big)?;
+        assert_eq!(big, term.get::<BigInt>()?);
+
+        let expr = context.new_term_ref();
+        expr.unify(Functor::new("+", 2))?;
+        expr.unify_arg(1, &big)?;
+        expr.unify_arg(2, 1_u64)?;
+        assert_eq!(
+            Number::BigInteger("1267650600228229401496703205377".to_string()),
+            context.eval(&expr)?
+        );
+
+        let small = context.term_from_string("42")?;
+        assert_eq!(BigInt::from(42), small.get::<BigInt>()?);
+        let rational = context.term_from_string("1r3")?;
+        assert!(rational.get::<BigInt>().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn unify_and_get_decimals() -> PrologResult<()> {
+        use crate::functor::*;
+        use rust_decimal::Decimal;
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        term.unify(Decimal::new(1234, 2))?;
+        assert_eq!(
+            Number::Rational("617".to_string(), "50".to_string()),
+            term.get::<Number>()?
+        );
+        assert_eq!(Decimal::new(1234, 2), term.get::<Decimal>()?);
+
+        let whole = context.new_term_ref();
+        whole.unify(Decimal::new(4200, 2))?;
+        assert_eq!(Number::Integer(42), whole.get::<Number>()?);
+
+        let [a, b, expr] = context.new_term_refs();
+        a.unify(Decimal::new(1, 1))?;
+        b.unify(Decimal::new(2, 1))?;
+        expr.unify(Functor::new("+", 2))?;
+        expr.unify_arg(1, &a)?;
+        expr.unify_arg(2, &b)?;
+        assert_eq!(
+            Number::Rational("3".to_string(), "10".to_string()),
+            context.eval(&expr)?
+        );
+
+        let third = context.term_from_string("1r3")?;
+        assert!(third.get::<Decimal>().unwrap_err().is_failure());
+        let float = context.term_from_string("0.5")?;
+        assert!(float.get::<Decimal>().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn succ_in_both_directions() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert_eq!(5, context.succ(Some(4), None)?);
+        assert_eq!(4, context.succ(None, Some(5))?);
+        assert_eq!(5, context.succ(Some(4), Some(5))?);
+        assert!(matches!(
+            context.succ(Some(4), Some(6)),
+            Err(PrologError::Failure)
+        ));
+        assert!(matches!(
+            context.succ(None, Some(0)),
+            Err(PrologError::Failure)
+        ));
+        assert!(context.succ(None, None).unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn plus_with_any_unknown() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert_eq!(7, context.plus(Some(3), Some(4), None)?);
+        assert_eq!(4, context.plus(Some(3), None, Some(7))?);
+        assert_eq!(3, context.plus(None, Some(4), Some(7))?);
+        assert_eq!(7, context.plus(Some(3), Some(4), Some(7))?);
+        assert!(matches!(
+            context.plus(Some(3), Some(4), Some(8)),
+            Err(PrologError::Failure)
+        ));
+        assert!(context
+            .plus(Some(3), None, None)
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn seeded_random_numbers_repeat() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let draw = || -> PrologResult<Vec<i64>> {
+            (0..10).map(|_| context.random_between(-5, 5)).collect()
+        };
+
+        context.set_random_seed(1234)?;
+        let first = draw()?;
+        context.set_random_seed(1234)?;
+        let second = draw()?;
+
+        assert_eq!(first, second);
+        assert!(first.iter().all(|n| (-5..=5).contains(n)));
+        assert!(matches!(
+            context.random_between(5, 4),
+            Err(PrologError::Failure)
+        ));
+
+        Ok(())
+    }
+}
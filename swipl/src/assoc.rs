@@ -0,0 +1,113 @@
+//! Helpers for working with assocs from `library(assoc)`.
+//!
+//! Assocs are AVL trees mapping keys to values. They are a common
+//! way of representing maps in prolog code. Working with them
+//! directly allows rust code to pass maps to and from prolog
+//! without converting them at every boundary.
+use crate::context::*;
+use crate::pred;
+use crate::result::*;
+use crate::term::*;
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Create a new empty assoc using `empty_assoc/1`.
+    pub fn empty_assoc(&self) -> PrologResult<Term> {
+        let assoc = self.new_term_ref();
+        self.call_once(pred!(empty_assoc / 1), [&assoc])?;
+
+        Ok(assoc)
+    }
+
+    /// Add a key-value pair to an assoc using `put_assoc/4`,
+    /// returning the new assoc.
+    ///
+    /// Assocs are immutable, so the original assoc is left as it
+    /// was. If the key was already present, its value is replaced in
+    /// the new assoc.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let assoc = context.empty_assoc()?;
+    /// let assoc = context.put_assoc(atom!("answer"), &assoc, 42_u64)?;
+    ///
+    /// let value = context.get_assoc(atom!("answer"), &assoc)?.unwrap();
+    /// assert_eq!(42, value.get::<u64>()?);
+    /// assert!(context.get_assoc(atom!("question"), &assoc)?.is_none());
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn put_assoc<K: Unifiable, V: Unifiable>(
+        &self,
+        key: K,
+        assoc: &Term,
+        value: V,
+    ) -> PrologResult<Term> {
+        let [key_term, value_term, new_assoc] = self.new_term_refs();
+        key_term.unify(key)?;
+        value_term.unify(value)?;
+        self.call_once(
+            pred!(put_assoc / 4),
+            [&key_term, assoc, &value_term, &new_assoc],
+        )?;
+
+        Ok(new_assoc)
+    }
+
+    /// Look up the value for a key in an assoc using `get_assoc/3`.
+    ///
+    /// If the key is not in the assoc, `None` is returned.
+    pub fn get_assoc<K: Unifiable>(&self, key: K, assoc: &Term) -> PrologResult<Option<Term>> {
+        let [key_term, value] = self.new_term_refs();
+        key_term.unify(key)?;
+        match self.call_once(pred!(get_assoc / 3), [&key_term, assoc, &value]) {
+            Ok(()) => Ok(Some(value)),
+            Err(PrologError::Failure) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn build_and_query_assoc() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let empty = context.empty_assoc()?;
+        let one = context.put_assoc(1_u64, &empty, atom!("one"))?;
+        let two = context.put_assoc(2_u64, &one, atom!("two"))?;
+        let replaced = context.put_assoc(1_u64, &two, atom!("uno"))?;
+
+        assert!(context.get_assoc(1_u64, &empty)?.is_none());
+        assert_eq!(
+            atom!("one"),
+            context.get_assoc(1_u64, &two)?.unwrap().get::<Atom>()?
+        );
+        assert_eq!(
+            atom!("two"),
+            context.get_assoc(2_u64, &two)?.unwrap().get::<Atom>()?
+        );
+        assert_eq!(
+            atom!("uno"),
+            context
+                .get_assoc(1_u64, &replaced)?
+                .unwrap()
+                .get::<Atom>()?
+        );
+
+        let pairs = context.new_term_ref();
+        context.call_once(pred!(assoc_to_keys / 2), [&replaced, &pairs])?;
+        assert_eq!(vec![1, 2], pairs.get::<Vec<u64>>()?);
+
+        Ok(())
+    }
+}
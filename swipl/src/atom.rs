@@ -43,6 +43,32 @@ impl Atom {
         Atom { atom }
     }
 
+    /// Wrap an `atom_t` that is owned elsewhere, increasing its reference count.
+    ///
+    /// Unlike [wrap](Atom::wrap), which takes over a reference that
+    /// the caller already holds, this registers a new reference, so
+    /// the atom stays valid for as long as the returned `Atom` is
+    /// around, even if the original owner releases it. This returns
+    /// `None` for the null atom handle 0.
+    ///
+    /// This will panic if no prolog engine is active on this thread.
+    ///
+    /// # Safety
+    /// This is unsafe because, apart from the null handle, no check
+    /// is done to ensure that the atom_t points at a valid atom. The
+    /// caller will have to ensure that it does, and that it won't be
+    /// garbage collected before this call.
+    pub unsafe fn try_from_ptr(atom: atom_t) -> Option<Atom> {
+        if atom == 0 {
+            return None;
+        }
+
+        assert_some_engine_is_active();
+        PL_register_atom(atom);
+
+        Some(Atom::wrap(atom))
+    }
+
     /// Create a new atom from the given string.
     ///
     /// This will panic if no prolog engine is active on this thread.
@@ -51,34 +77,40 @@ impl Atom {
     /// count on that atom and then return the existing atom.
     pub fn new(name: &str) -> Atom {
         assert_swipl_is_initialized();
-        // there's a worrying bit of information in the documentation.
-        // It says that in some cases for small strings,
-        // PL_new_atom_mbchars will recalculate the size of the string
-        // using strlen. In that case we need to give it a
-        // nul-terminated string.
-        const S_USIZE: usize = std::mem::size_of::<usize>();
-        let atom = if name.len() == S_USIZE - 1 {
-            let mut buf: [u8; S_USIZE] = [0; S_USIZE];
-            buf[..name.len()].clone_from_slice(name.as_bytes());
-
-            unsafe {
-                PL_new_atom_mbchars(
-                    REP_UTF8.try_into().unwrap(),
-                    name.len(),
-                    buf.as_ptr() as *const c_char,
-                )
-            }
-        } else {
-            unsafe {
-                PL_new_atom_mbchars(
-                    REP_UTF8.try_into().unwrap(),
-                    name.len(),
-                    name.as_ptr() as *const c_char,
-                )
-            }
-        };
 
-        unsafe { Atom::wrap(atom) }
+        unsafe { Atom::wrap(new_atom_mbchars(REP_UTF8, name.as_bytes())) }
+    }
+
+    /// Create a new atom from the given UTF-8 encoded bytes.
+    ///
+    /// Unlike a rust string, the bytes may contain encoded lone
+    /// surrogates, so the output of [name_bytes](Atom::name_bytes)
+    /// can always be turned back into the same atom. For any other
+    /// invalid UTF-8, this returns `None`.
+    ///
+    /// This will panic if SWI-Prolog has not been initialized.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Atom> {
+        assert_swipl_is_initialized();
+
+        if !is_utf8_with_surrogates(bytes) {
+            return None;
+        }
+
+        unsafe { Some(Atom::wrap(new_atom_mbchars(REP_UTF8, bytes))) }
+    }
+
+    /// Create a new atom from the given bytes, interpreting each byte as an ISO Latin-1 character.
+    ///
+    /// Any byte sequence is accepted, which makes this suitable for
+    /// text of unknown origin. Bytes 0x80 to 0xff become the
+    /// characters U+0080 to U+00FF, so for UTF-8 text, use
+    /// [from_bytes](Atom::from_bytes) or [new](Atom::new) instead.
+    ///
+    /// This will panic if SWI-Prolog has not been initialized.
+    pub fn from_latin1(bytes: &[u8]) -> Atom {
+        assert_swipl_is_initialized();
+
+        unsafe { Atom::wrap(new_atom_mbchars(REP_ISO_LATIN_1, bytes)) }
     }
 
     /// Return the underlying `atom_t` which SWI-Prolog uses to refer to the atom.
@@ -89,6 +121,10 @@ impl Atom {
     /// Retrieve the name of this atom, that is, the string with which it was created.
     ///
     /// This will panic if no prolog engine is active on this thread.
+    /// It will also panic if the name can't be represented as a rust
+    /// string, which happens for atoms containing lone surrogate code
+    /// points. Use [name_lossy](Atom::name_lossy) for atoms whose
+    /// text comes from outside sources.
     pub fn name(&self) -> String {
         // NOTE: This code is terrible. There should be no reason to
         // go through term to get a string out, except that's the only
@@ -109,12 +145,97 @@ impl Atom {
         name.unwrap()
     }
 
+    /// Retrieve the name of this atom, replacing anything that is not valid UTF-8 with U+FFFD.
+    ///
+    /// This never panics on the contents of the atom. For blobs that
+    /// have no text representation, the name is empty.
+    ///
+    /// This will panic if no prolog engine is active on this thread.
+    pub fn name_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.name_bytes()).into_owned()
+    }
+
+    /// Retrieve the name of this atom as UTF-8 encoded bytes.
+    ///
+    /// Unlike with [name](Atom::name), the bytes are not checked,
+    /// so code points that rust strings can't hold, such as lone
+    /// surrogates, are returned in their raw encoded form. For blobs
+    /// that have no text representation, this is empty.
+    ///
+    /// This will panic if no prolog engine is active on this thread.
+    pub fn name_bytes(&self) -> Vec<u8> {
+        assert_some_engine_is_active();
+
+        unsafe {
+            let temp_term_ref = PL_new_term_ref();
+            PL_put_atom(temp_term_ref, self.atom);
+            let mut len = 0;
+            let mut ptr = std::ptr::null_mut();
+            let result = PL_get_nchars(
+                temp_term_ref,
+                &mut len,
+                &mut ptr,
+                CVT_ATOM | REP_UTF8 | BUF_DISCARDABLE,
+            );
+            let bytes = if result == 0 {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts(ptr as *const u8, len).to_vec()
+            };
+            PL_reset_term_refs(temp_term_ref);
+
+            bytes
+        }
+    }
+
     /// Increase the reference counter for this atom.
     pub(crate) fn increment_refcount(&self) {
         unsafe { PL_register_atom(self.atom) }
     }
 }
 
+/// Check that the bytes are UTF-8, allowing encoded surrogates, which SWI-Prolog may put in atoms.
+fn is_utf8_with_surrogates(mut bytes: &[u8]) -> bool {
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(_) => return true,
+            Err(e) => match &bytes[e.valid_up_to()..] {
+                [0xed, 0xa0..=0xbf, 0x80..=0xbf, rest @ ..] => bytes = rest,
+                _ => return false,
+            },
+        }
+    }
+}
+
+/// Create an atom from text in the given representation.
+///
+/// # Safety
+/// SWI-Prolog must have been initialized.
+unsafe fn new_atom_mbchars(rep: u32, bytes: &[u8]) -> atom_t {
+    // there's a worrying bit of information in the documentation.
+    // It says that in some cases for small strings,
+    // PL_new_atom_mbchars will recalculate the size of the string
+    // using strlen. In that case we need to give it a
+    // nul-terminated string.
+    const S_USIZE: usize = std::mem::size_of::<usize>();
+    if bytes.len() == S_USIZE - 1 {
+        let mut buf: [u8; S_USIZE] = [0; S_USIZE];
+        buf[..bytes.len()].clone_from_slice(bytes);
+
+        PL_new_atom_mbchars(
+            rep.try_into().unwrap(),
+            bytes.len(),
+            buf.as_ptr() as *const c_char,
+        )
+    } else {
+        PL_new_atom_mbchars(
+            rep.try_into().unwrap(),
+            bytes.len(),
+            bytes.as_ptr() as *const c_char,
+        )
+    }
+}
+
 impl ToString for Atom {
     fn to_string(&self) -> String {
         self.name()
@@ -144,6 +265,31 @@ impl Drop for Atom {
     }
 }
 
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Retrieve all atoms that are currently in the atom table, using `current_atom/1`.
+    ///
+    /// This is meant for debugging tools, such as looking for atoms
+    /// that are leaked by some workload. The atom table can be large,
+    /// so don't call this on a hot path. Each returned atom holds a
+    /// reference, which keeps it from being garbage collected until
+    /// it is dropped.
+    pub fn current_atoms(&self) -> PrologResult<Vec<Atom>> {
+        let frame = self.open_frame();
+        let [atom, goal, atoms] = frame.new_term_refs();
+        goal.unify(crate::functor!("current_atom/1"))?;
+        goal.unify_arg(1, &atom)?;
+        frame.call_once(crate::pred!(findall / 3), [&atom, &goal, &atoms])?;
+
+        let result = frame
+            .term_list_iter(&atoms)
+            .filter_map(|atom| atom.get::<Atom>().ok())
+            .collect();
+        frame.discard();
+
+        Ok(result)
+    }
+}
+
 unifiable! {
     (self:Atom, term) => {
         let result = unsafe { PL_unify_atom(term.term_ptr(), self.atom) };
@@ -661,6 +807,63 @@ mod tests {
         assert_eq!(a1, a3);
     }
 
+    #[test]
+    fn atom_from_ptr_registers_reference() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let a1 = Atom::new("an atom owned elsewhere");
+        let a2 = unsafe { Atom::try_from_ptr(a1.atom_ptr()) }.unwrap();
+        std::mem::drop(a1);
+        assert_eq!("an atom owned elsewhere", a2.name());
+
+        assert!(unsafe { Atom::try_from_ptr(0) }.is_none());
+    }
+
+    #[test]
+    fn list_current_atoms() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let atom = Atom::new("a fresh atom for the atom table");
+        let atoms = context.current_atoms()?;
+        assert!(atoms.contains(&atom));
+        assert!(atoms.contains(&Atom::new("true")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn non_ascii_atoms() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let atom = Atom::new("caf\u{e9} \u{1f600}");
+        assert_eq!("caf\u{e9} \u{1f600}", atom.name());
+        assert_eq!("caf\u{e9} \u{1f600}", atom.name_lossy());
+        assert_eq!("caf\u{e9} \u{1f600}".as_bytes(), &atom.name_bytes()[..]);
+
+        assert_eq!(Some(atom.clone()), Atom::from_bytes(&atom.name_bytes()));
+        assert_eq!(None, Atom::from_bytes(b"caf\xe9"));
+
+        let surrogate = Atom::from_bytes(b"a\xed\xa0\x80").unwrap();
+        assert_eq!(b"a\xed\xa0\x80", &surrogate.name_bytes()[..]);
+        assert_eq!(
+            Some(surrogate.clone()),
+            Atom::from_bytes(&surrogate.name_bytes())
+        );
+        assert!(surrogate.name_lossy().starts_with('a'));
+
+        let latin1 = Atom::from_latin1(b"caf\xe9 \xff");
+        assert_eq!("caf\u{e9} \u{ff}", latin1.name());
+        assert_eq!(Atom::new("caf\u{e9} \u{ff}"), latin1);
+
+        let short = Atom::from_latin1(b"1234567");
+        assert_eq!("1234567", short.name_lossy());
+        assert_eq!(Some(short), Atom::from_bytes(b"1234567"));
+    }
+
     use swipl_macros::atom;
     #[test]
     fn inline_atom_through_macro_ident() {
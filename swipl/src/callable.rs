@@ -1,14 +1,18 @@
 //! Support for calling into prolog or prolog-like code.
+use crate::atom::*;
 use crate::context::*;
 use crate::engine::*;
 use crate::fli::*;
 use crate::functor::*;
 use crate::module::*;
 use crate::predicate::*;
+use crate::record::*;
 use crate::result::*;
 use crate::term::*;
+use std::collections::HashMap;
 use std::convert::TryInto;
-use std::os::raw::c_void;
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
 use std::sync::atomic::{AtomicPtr, Ordering};
 use thiserror::Error;
 
@@ -65,6 +69,17 @@ impl<const N: usize> Callable<N> for LazyCallablePredicate<N> {
     ) -> Context<'a, OpenQuery> {
         self.as_callable().open(context, module, args)
     }
+
+    fn open_with_options<'a, C: ContextType>(
+        self,
+        context: &'a Context<C>,
+        module: Option<Module>,
+        options: QueryOptions,
+        args: [&Term; N],
+    ) -> Context<'a, OpenQuery> {
+        self.as_callable()
+            .open_with_options(context, module, options, args)
+    }
 }
 
 impl<'a, const N: usize> Callable<N> for &'a LazyCallablePredicate<N> {
@@ -78,6 +93,17 @@ impl<'a, const N: usize> Callable<N> for &'a LazyCallablePredicate<N> {
     ) -> Context<'b, OpenQuery> {
         self.as_callable().open(context, module, args)
     }
+
+    fn open_with_options<'b, C: ContextType>(
+        self,
+        context: &'b Context<C>,
+        module: Option<Module>,
+        options: QueryOptions,
+        args: [&Term; N],
+    ) -> Context<'b, OpenQuery> {
+        self.as_callable()
+            .open_with_options(context, module, options, args)
+    }
 }
 
 /// Error type for turning a [Predicate](crate::predicate::Predicate) into a [CallablePredicate].
@@ -143,12 +169,118 @@ pub trait Callable<const N: usize> {
         module: Option<Module>,
         args: [&Term; N],
     ) -> Context<'a, Self::ContextType>;
+
+    /// Open this callable with the given query options.
+    ///
+    /// Callables for which the options have no meaning may ignore
+    /// them. By default, this is the same as [open](Callable::open).
+    fn open_with_options<'a, C: ContextType>(
+        self,
+        context: &'a Context<C>,
+        module: Option<Module>,
+        _options: QueryOptions,
+        args: [&Term; N],
+    ) -> Context<'a, Self::ContextType>
+    where
+        Self: Sized,
+    {
+        self.open(context, module, args)
+    }
+}
+
+/// Options for opening a query.
+///
+/// These map onto the flags of `PL_open_query()`. The default
+/// options, which are used by [Context::open], catch exceptions
+/// raised by the query and leave debug mode as it is.
+///
+/// Example:
+/// ```
+/// # use swipl::prelude::*;
+/// # fn main() -> PrologResult<()> {
+/// #  let engine = Engine::new();
+/// #  let activation = engine.activate();
+/// #  let context: Context<_> = activation.into();
+/// let options = QueryOptions::new().debug(false);
+/// let x = context.new_term_ref();
+/// let query = context.open_with_options(pred!(succ / 2), None, options, [&term! {context: 1}?, &x]);
+/// query.next_solution()?;
+/// query.cut()?;
+/// assert_eq!(2, x.get::<u64>()?);
+/// #  Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct QueryOptions {
+    pass_exceptions: bool,
+    debug: bool,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryOptions {
+    /// Create the default query options.
+    pub fn new() -> Self {
+        Self {
+            pass_exceptions: false,
+            debug: true,
+        }
+    }
+
+    /// Set whether exceptions should be passed on to the environment.
+    ///
+    /// By default, exceptions are caught inside the query
+    /// (`PL_Q_CATCH_EXCEPTION`). When passing exceptions
+    /// (`PL_Q_PASS_EXCEPTION`), the exception is also visible to
+    /// prolog code further up the stack, such as a `catch/3` around
+    /// the foreign predicate which opened this query, and the
+    /// debugger may consider it to be uncaught. Either way, an
+    /// exception is returned as `Err(PrologError::Exception)`.
+    pub fn pass_exceptions(mut self, pass: bool) -> Self {
+        self.pass_exceptions = pass;
+
+        self
+    }
+
+    /// Set whether the query may be debugged.
+    ///
+    /// When set to false, the query runs with `PL_Q_NODEBUG`, which
+    /// switches off the debugger and tracer for its duration. This
+    /// is useful for queries that are an implementation detail,
+    /// which should not show up while tracing the surrounding prolog
+    /// code. There is no flag to switch debug mode on for a single
+    /// query, for that, use `debug/0` instead.
+    pub fn debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+
+        self
+    }
+
+    fn flags(&self) -> u32 {
+        let mut flags = PL_Q_NORMAL | PL_Q_EXT_STATUS;
+        if self.pass_exceptions {
+            flags |= PL_Q_PASS_EXCEPTION;
+        } else {
+            flags |= PL_Q_CATCH_EXCEPTION;
+        }
+        if !self.debug {
+            flags |= PL_Q_NODEBUG;
+        }
+
+        flags
+    }
 }
 
 /// An open query.
 pub struct OpenQuery {
     qid: qid_t,
     closed: bool,
+    args: term_t,
+    arity: usize,
 }
 
 /// An open call.
@@ -182,14 +314,26 @@ pub unsafe trait OpenCall: Sized {
     /// Cut the query, keeping all data it has created.
     ///
     /// Any unifications the query did to terms from parent contexts
-    /// will be retained.
-    fn cut(this: Context<Self>);
+    /// will be retained. If a cleanup handler of the query raised an
+    /// exception, or an exception raised by the query itself is still
+    /// pending, this is returned in the `Err` part of the
+    /// `PrologResult`.
+    fn cut(this: Context<Self>) -> PrologResult<()>;
 
     /// Discard the query, discarding all data it has created.
     ///
     /// Any unifications the query did to terms from parent contexts
-    /// will be discarded.
-    fn discard(this: Context<Self>);
+    /// will be discarded. If a cleanup handler of the query raised an
+    /// exception, or an exception raised by the query itself is still
+    /// pending, this is returned in the `Err` part of the
+    /// `PrologResult`.
+    fn discard(this: Context<Self>) -> PrologResult<()>;
+
+    /// Retrieve the argument terms this call was opened with.
+    ///
+    /// After a solution has been retrieved, these hold its
+    /// bindings.
+    fn arguments<'b>(this: &'b Context<Self>) -> Vec<Term<'b>>;
 }
 
 impl<'a, C: OpenCall> Context<'a, C> {
@@ -206,25 +350,92 @@ impl<'a, C: OpenCall> Context<'a, C> {
     /// Cut the query, keeping all data it has created.
     ///
     /// Any unifications the query did to terms from parent contexts
-    /// will be retained.
-    pub fn cut(self) {
+    /// will be retained. Cutting the query runs the cleanup handlers
+    /// of any `setup_call_cleanup/3` calls that are still
+    /// active. If one of these raised an exception, this is returned
+    /// as `Err(PrologError::Exception)`, and the exception is left
+    /// pending on the parent context. The same is returned if the
+    /// query itself raised an exception earlier, for example from
+    /// [next_solution](Context::next_solution), which is still
+    /// pending.
+    pub fn cut(self) -> PrologResult<()> {
         C::cut(self)
     }
 
     /// Discard the query, discarding all data it has created.
     ///
     /// Any unifications the query did to terms from parent contexts
-    /// will be discarded.
-    pub fn discard(self) {
+    /// will be discarded. Like with [cut](Context::cut), an exception
+    /// raised by a cleanup handler, or an earlier exception of the
+    /// query that is still pending, is returned as
+    /// `Err(PrologError::Exception)`.
+    pub fn discard(self) -> PrologResult<()> {
         C::discard(self)
     }
 
+    /// Retrieve the next solution, converting the arguments of the query into a typed row.
+    ///
+    /// The row is a tuple of [TermGetable] types. A row of `n`
+    /// elements is retrieved from the last `n` arguments of the
+    /// query, in order, as by prolog convention those are the
+    /// outputs. For example, a row of two elements for a query of
+    /// `foo/4` holds its third and fourth argument. A row with more
+    /// elements than the query has arguments raises
+    /// `error(domain_error(solution_row, N), _)`, where `N` is the
+    /// size of the row, without retrieving a solution.
+    ///
+    /// This returns `None` once there are no more solutions,
+    /// `Some(Err(..))` on an exception or if an argument could not be
+    /// converted, and `Some(Ok(row))` otherwise.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let [length, name] = context.new_term_refs();
+    /// let query = context.open(pred!(sub_atom/5), [&term!{context: ab}?, &term!{context: 0}?, &length, &term!{context: _}?, &name]);
+    /// let mut rows = Vec::new();
+    /// while let Some(row) = query.next::<(u64, u64, String)>() {
+    ///     rows.push(row?);
+    /// }
+    /// query.cut()?;
+    ///
+    /// assert_eq!(
+    ///     vec![(0, 2, "".to_string()), (1, 1, "a".to_string()), (2, 0, "ab".to_string())],
+    ///     rows
+    /// );
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn next<R: SolutionRow>(&self) -> Option<PrologResult<R>> {
+        let args = C::arguments(self);
+        if R::ARITY > args.len() {
+            // an open query can't raise exceptions itself, so this
+            // goes through an unmanaged context instead.
+            let context = unsafe { unmanaged_engine_context() };
+            let culprit = context.new_term_ref();
+            let result = culprit
+                .unify(R::ARITY as u64)
+                .and_then(|_| context.raise_domain_error("solution_row", &culprit));
+            unsafe { culprit.reset() };
+
+            return Some(result);
+        }
+
+        match self.next_solution() {
+            Err(PrologError::Failure) => None,
+            Err(e) => Some(Err(e)),
+            Ok(_) => Some(R::get_from(&args[args.len() - R::ARITY..])),
+        }
+    }
+
     /// Retrieve one result, and then cut.
     pub fn once(self) -> PrologResult<()> {
         self.next_solution()?;
-        self.cut();
-
-        Ok(())
+        self.cut()
     }
 
     /// Retrieve one result, ignoring failures, and then cut.
@@ -234,10 +445,676 @@ impl<'a, C: OpenCall> Context<'a, C> {
         if let Err(PrologError::Exception) = self.next_solution() {
             Err(PrologError::Exception)
         } else {
-            self.cut();
+            self.cut()
+        }
+    }
+
+    /// Iterate over the solutions of this query.
+    ///
+    /// Each item signals that the query has produced a solution,
+    /// whose bindings can then be read from the terms the query was
+    /// opened with. Those bindings are undone once the iterator moves
+    /// on to the next solution. The iterator stops after the last
+    /// solution or on failure. An exception is returned as an
+    /// `Err(PrologError::Exception)`, after which the iterator stops
+    /// as well.
+    ///
+    /// Any terms created while handling a solution remain on the
+    /// prolog stack until the query is closed. When enumerating a
+    /// large amount of solutions, use
+    /// [solutions_framed](Context::solutions_framed) instead.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let x = context.new_term_ref();
+    /// let query = context.open(pred!(between/3), [&term!{context: 1}?, &term!{context: 3}?, &x]);
+    /// let mut sum = 0;
+    /// for solution in query.solutions() {
+    ///     solution?;
+    ///     sum += x.get::<u64>()?;
+    /// }
+    /// query.cut()?;
+    ///
+    /// assert_eq!(6, sum);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn solutions<'b>(&'b self) -> Solutions<'b, 'a, C> {
+        Solutions {
+            query: self,
+            done: false,
+        }
+    }
+
+    /// Iterate over the solutions of this query, extracting data
+    /// from each solution inside of its own frame.
+    ///
+    /// For each solution, a frame is opened and the given function
+    /// is called with that frame. This function should copy out
+    /// whatever data it needs from the solution. Afterwards, the
+    /// frame is discarded, reclaiming any terms that were created
+    /// while extracting. This keeps the prolog stack from growing
+    /// with each solution, which matters when enumerating a large
+    /// amount of them.
+    ///
+    /// The iterator stops after the last solution or on failure. An
+    /// exception is returned as an `Err(PrologError::Exception)`,
+    /// after which the iterator stops as well.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let x = context.new_term_ref();
+    /// let query = context.open(pred!(between/3), [&term!{context: 1}?, &term!{context: 3}?, &x]);
+    /// let results: Vec<u64> = query
+    ///     .solutions_framed(|_frame| x.get::<u64>())
+    ///     .collect::<PrologResult<_>>()?;
+    /// query.cut()?;
+    ///
+    /// assert_eq!(vec![1, 2, 3], results);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn solutions_framed<'b, R, F>(&'b self, extract: F) -> FramedSolutions<'b, 'a, C, F>
+    where
+        F: FnMut(&Context<Frame>) -> PrologResult<R>,
+    {
+        FramedSolutions {
+            query: self,
+            extract,
+            done: false,
+        }
+    }
+
+    /// Collect all solutions of this query, along with their
+    /// position and whether they were known to be the last solution.
+    ///
+    /// Solutions are collected in the order prolog produced
+    /// them. Like with [solutions_framed](Context::solutions_framed),
+    /// the given function is called to extract data from each solution
+    /// inside of its own frame.
+    ///
+    /// A solution is marked as last when prolog reported it as such,
+    /// meaning no choice points were left. If the query instead
+    /// fails after its final solution, none of the solutions will be
+    /// marked as last. This is useful for finding out whether a
+    /// predicate is leaving choice points behind.
+    ///
+    /// An exception in the query or in the extract function is
+    /// returned as an error. The query is left open either way.
+    pub fn collect_indexed<R, F>(&self, mut extract: F) -> PrologResult<Vec<IndexedSolution<R>>>
+    where
+        F: FnMut(&Context<Frame>) -> PrologResult<R>,
+    {
+        let mut solutions = Vec::new();
+        loop {
+            let more = match self.next_solution() {
+                Err(PrologError::Failure) => break,
+                Err(e) => return Err(e),
+                Ok(more) => more,
+            };
+
+            let frame = self.open_frame();
+            let value = extract(&frame);
+            frame.discard();
+            solutions.push(IndexedSolution {
+                index: solutions.len(),
+                last: !more,
+                value: value?,
+            });
+
+            if !more {
+                break;
+            }
+        }
+
+        Ok(solutions)
+    }
+}
+
+/// A row of values retrieved from the arguments of a query, as used by [Context::next].
+///
+/// This is implemented for tuples of up to 8 gettable elements.
+pub trait SolutionRow: Sized {
+    /// The number of arguments this row is retrieved from.
+    const ARITY: usize;
+
+    /// Get each element of this row from the corresponding term.
+    fn get_from(terms: &[Term]) -> PrologResult<Self>;
+}
+
+macro_rules! solution_row_tuple {
+    ($arity:literal; $($t:ident $i:tt),*) => {
+        impl<$($t: TermGetable),*> SolutionRow for ($($t,)*) {
+            const ARITY: usize = $arity;
+
+            fn get_from(terms: &[Term]) -> PrologResult<Self> {
+                Ok(($(terms[$i].get::<$t>()?,)*))
+            }
+        }
+    };
+}
+
+solution_row_tuple!(1; A 0);
+solution_row_tuple!(2; A 0, B 1);
+solution_row_tuple!(3; A 0, B 1, C 2);
+solution_row_tuple!(4; A 0, B 1, C 2, D 3);
+solution_row_tuple!(5; A 0, B 1, C 2, D 3, E 4);
+solution_row_tuple!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+solution_row_tuple!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+solution_row_tuple!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// A solution collected by [collect_indexed](Context::collect_indexed).
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedSolution<T> {
+    /// The position of this solution, starting at 0.
+    pub index: usize,
+    /// Whether prolog reported this solution as the last one.
+    pub last: bool,
+    /// The value extracted from this solution.
+    pub value: T,
+}
+
+/// An iterator over the solutions of a query.
+///
+/// This is returned by [solutions](Context::solutions).
+pub struct Solutions<'b, 'a, C: OpenCall> {
+    query: &'b Context<'a, C>,
+    done: bool,
+}
+
+impl<'b, 'a, C: OpenCall> Iterator for Solutions<'b, 'a, C> {
+    type Item = PrologResult<()>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.query.next_solution() {
+            Err(PrologError::Failure) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+            Ok(more) => {
+                self.done = !more;
+                Some(Ok(()))
+            }
+        }
+    }
+}
+
+/// An iterator over the solutions of a query, where each solution is
+/// extracted from within its own frame.
+///
+/// This is returned by [solutions_framed](Context::solutions_framed).
+pub struct FramedSolutions<'b, 'a, C: OpenCall, F> {
+    query: &'b Context<'a, C>,
+    extract: F,
+    done: bool,
+}
+
+impl<'b, 'a, C: OpenCall, R, F> Iterator for FramedSolutions<'b, 'a, C, F>
+where
+    F: FnMut(&Context<Frame>) -> PrologResult<R>,
+{
+    type Item = PrologResult<R>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.query.next_solution() {
+            Err(PrologError::Failure) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+            Ok(more) => {
+                self.done = !more;
+                let frame = self.query.open_frame();
+                let result = (self.extract)(&frame);
+                frame.discard();
+
+                Some(result)
+            }
+        }
+    }
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Open a generator over the solutions of the given goal.
+    ///
+    /// The goal is called using `call/1`. For each solution, the
+    /// value of `out` is retrieved as a `G`, making this a lazy
+    /// counterpart to `findall/3`. Solutions are only computed
+    /// as the generator is advanced, so this can be used with goals
+    /// that produce a huge or even infinite amount of solutions.
+    ///
+    /// The generator holds the underlying query open, which means
+    /// that this context remains inactive until the generator is
+    /// dropped. Dropping it cuts the query.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let goal = term!{context: between(1, inf, X)}?;
+    /// let out = context.new_term_ref();
+    /// goal.unify_arg(3, &out)?;
+    ///
+    /// let first: Vec<u64> = context
+    ///     .generator(&goal, &out)
+    ///     .take(3)
+    ///     .collect::<PrologResult<_>>()?;
+    ///
+    /// assert_eq!(vec![1, 2, 3], first);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn generator<G: TermGetable>(&self, goal: &Term, out: &Term) -> Generator<G> {
+        let out_ref = self.new_term_ref();
+        out_ref
+            .put(out)
+            .expect("putting a term into a fresh term ref should work");
+        let query = self.open(crate::pred!(call / 1), [goal]);
+
+        Generator {
+            query: Some(query),
+            out: out_ref,
+            done: false,
+            _value: PhantomData,
+        }
+    }
+
+    /// Solve a goal given as text, generating the values of one of its variables.
+    ///
+    /// The goal is parsed with `term_string/3`, after which this
+    /// works like [generator](Context::generator), with `variable`
+    /// being the name of the variable in the goal whose values are
+    /// generated. This is the quickest way to get typed solutions
+    /// out of prolog, as no terms have to be built by hand.
+    ///
+    /// A syntax error in the goal is returned as an exception. If
+    /// the goal does not contain the given variable, an
+    /// `existence_error(variable, Name)` is raised.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let squares: Vec<u64> = context
+    ///     .solve("between(1, 4, X), Y is X * X", "Y")?
+    ///     .collect::<PrologResult<_>>()?;
+    ///
+    /// assert_eq!(vec![1, 4, 9, 16], squares);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn solve<G: TermGetable>(&self, goal: &str, variable: &str) -> PrologResult<Generator<G>> {
+        let (goal_term, bindings) = self.parse_goal(goal)?;
+
+        let out = match self.term_list_iter(&bindings).find(
+            |binding| matches!(binding.get_arg::<Atom>(1), Ok(name) if name.name() == variable),
+        ) {
+            Some(out) => out,
+            None => {
+                let name = self.new_term_ref();
+                name.unify(Atom::new(variable))?;
+                return self.raise_existence_error("variable", &name);
+            }
+        };
+        let out_var = self.new_term_ref();
+        out.unify_arg(2, &out_var)?;
+
+        Ok(self.generator(&goal_term, &out_var))
+    }
+
+    /// Open a query for a goal given as text, exposing the bindings of its named variables.
+    ///
+    /// The goal is parsed with `term_string/3`, recording the named
+    /// variables like `read_term/2` does with `variable_names`. Each
+    /// solution of the returned [StringQuery] is a map from variable
+    /// name to the term that variable is bound to. Anonymous
+    /// variables (`_`) are not included. This is convenient for
+    /// REPL-style applications, where goals come from a user.
+    ///
+    /// A syntax error in the goal is returned as an exception.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let query = context.query_from_string("member(X-Y, [1-a, 2-b])")?;
+    /// let mut pairs = Vec::new();
+    /// for bindings in query {
+    ///     let bindings = bindings?;
+    ///     pairs.push((bindings["X"].get::<u64>()?, bindings["Y"].get::<Atom>()?));
+    /// }
+    ///
+    /// assert_eq!(vec![(1, atom!("a")), (2, atom!("b"))], pairs);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn query_from_string(&self, goal: &str) -> PrologResult<StringQuery> {
+        let (goal_term, bindings) = self.parse_goal(goal)?;
+
+        let mut variables = Vec::new();
+        for binding in self.term_list_iter(&bindings) {
+            let name: Atom = binding.get_arg(1)?;
+            let var = self.new_term_ref();
+            binding.unify_arg(2, &var)?;
+            variables.push((name.name(), var));
+        }
+        let query = self.open(crate::pred!(call / 1), [&goal_term]);
+
+        Ok(StringQuery {
+            query,
+            variables,
+            done: false,
+        })
+    }
+
+    /// Parse a goal with `term_string/3`, also returning its variable names as a list of `Name = Var` pairs.
+    fn parse_goal(&self, goal: &str) -> PrologResult<(Term, Term)> {
+        let [goal_term, text, option, options, bindings] = self.new_term_refs();
+        text.unify(goal)?;
+        option.unify(crate::functor!("variable_names/1"))?;
+        option.unify_arg(1, &bindings)?;
+        options.unify(&[&option][..])?;
+        self.call_once(crate::pred!(term_string / 3), [&goal_term, &text, &options])?;
+
+        Ok((goal_term, bindings))
+    }
+
+    /// Retrieve the value of `out` for at most the first `n` solutions of `goal`.
+    ///
+    /// Each value is extracted as a `G` right after its solution is
+    /// found, before backtracking for the next one. The query is
+    /// closed once `n` values have been collected, so no more
+    /// solutions are computed than needed. This makes it suitable for paging
+    /// through the results of an expensive or infinite goal.
+    ///
+    /// If a value can't be retrieved as a `G`, this returns
+    /// `Err(PrologError::Failure)`, and an exception raised by the
+    /// goal is returned as such. Having fewer than `n` solutions is
+    /// not an error. Bindings made by the goal are not kept.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let x = context.new_term_ref();
+    /// let goal = term! {context: between(1, inf, #&x)}?;
+    /// let first: Vec<u64> = context.take_values(&goal, &x, 3)?;
+    /// assert_eq!(vec![1, 2, 3], first);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn take_values<G: TermGetable>(
+        &self,
+        goal: &Term,
+        out: &Term,
+        n: usize,
+    ) -> PrologResult<Vec<G>> {
+        let mut values = Vec::new();
+        let query = self.open(crate::pred!(call / 1), [goal]);
+        while values.len() < n {
+            match query.next_solution() {
+                Ok(more) => {
+                    let frame = query.open_frame();
+                    let value = out.get::<G>();
+                    frame.discard();
+                    values.push(value?);
+                    if !more {
+                        break;
+                    }
+                }
+                Err(PrologError::Failure) => break,
+                Err(e) => return Err(e),
+            }
+        }
+        query.discard()?;
+
+        Ok(values)
+    }
+}
+
+/// A query for a goal parsed from text, iterating over the bindings of its named variables.
+///
+/// This is returned by
+/// [query_from_string](Context::query_from_string). Each item is a
+/// map from variable name to its binding in that solution. The terms
+/// in the map are only valid until the iterator moves on, as
+/// backtracking undoes their bindings. The iterator stops when there
+/// are no more solutions, or after returning an exception.
+///
+/// Dropping the query discards it.
+pub struct StringQuery<'a> {
+    query: Context<'a, OpenQuery>,
+    variables: Vec<(String, Term<'a>)>,
+    done: bool,
+}
+
+impl<'a> StringQuery<'a> {
+    /// The names of the variables in the goal, in the order they first occur.
+    pub fn variable_names(&self) -> impl Iterator<Item = &str> {
+        self.variables.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Cut the query, keeping the bindings of the current solution.
+    pub fn cut(self) -> PrologResult<()> {
+        self.query.cut()
+    }
+
+    /// Discard the query, undoing all its bindings.
+    pub fn discard(self) -> PrologResult<()> {
+        self.query.discard()
+    }
+}
+
+impl<'a> Iterator for StringQuery<'a> {
+    type Item = PrologResult<HashMap<String, Term<'a>>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.query.next_solution() {
+            Err(PrologError::Failure) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+            Ok(more) => {
+                self.done = !more;
+                Some(Ok(self.variables.iter().cloned().collect()))
+            }
+        }
+    }
+}
+
+/// A lazy iterator over the solutions of a goal.
+///
+/// This is returned by [generator](Context::generator). Each call to
+/// `next` retrieves the next solution and returns the value of the
+/// output term as a `G`. If this value could not be retrieved as a
+/// `G`, that particular item will be an `Err(PrologError::Failure)`,
+/// but iteration may continue. The iterator stops when there are no
+/// more solutions, or after returning an exception.
+///
+/// The generator borrows the context it was opened from, so it can
+/// never outlive it. The underlying query is cut on drop.
+pub struct Generator<'a, G> {
+    query: Option<Context<'a, OpenQuery>>,
+    out: Term<'a>,
+    done: bool,
+    _value: PhantomData<G>,
+}
+
+impl<'a, G: TermGetable> Iterator for Generator<'a, G> {
+    type Item = PrologResult<G>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let query = self.query.as_ref()?;
+
+        match query.next_solution() {
+            Err(PrologError::Failure) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+            Ok(more) => {
+                self.done = !more;
+                let frame = query.open_frame();
+                let result = self.out.get::<G>();
+                frame.discard();
+
+                Some(result)
+            }
+        }
+    }
+}
 
-            Ok(())
+impl<'a, G> Drop for Generator<'a, G> {
+    fn drop(&mut self) {
+        if let Some(query) = self.query.take() {
+            // an exception raised while cutting stays pending on the context
+            let _ = query.cut();
+        }
+    }
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Compile a goal into a [CompiledGoal], which can be called many times.
+    ///
+    /// The goal is recorded together with its free variables, in the
+    /// order that `term_variables/2` returns them. Each call to the
+    /// compiled goal recreates the goal from this record, and binds
+    /// these variables to the given arguments. This saves having to
+    /// construct or parse the same goal over and over again, when
+    /// only the data it operates on differs between calls.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let goal = context.compile_goal(&term! {context: atom_length(_, _)}?)?;
+    /// assert_eq!(2, goal.arity());
+    ///
+    /// let length = context.new_term_ref();
+    /// goal.call(&context, &[&term! {context: hello}?, &length])?;
+    /// assert_eq!(5, length.get::<u64>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn compile_goal(&self, goal: &Term) -> PrologResult<CompiledGoal> {
+        let frame = self.open_frame();
+        let [variables, template] = frame.new_term_refs();
+        frame.call_once(crate::pred!(term_variables / 2), [goal, &variables])?;
+        let arity = frame.term_list_iter(&variables).count();
+
+        template.unify(crate::functor!("-/2"))?;
+        template.unify_arg(1, &variables)?;
+        template.unify_arg(2, goal)?;
+        let record = template.record();
+        frame.close();
+
+        Ok(CompiledGoal { record, arity })
+    }
+}
+
+/// A goal that was compiled for repeated calling.
+///
+/// This is returned by [compile_goal](Context::compile_goal).
+pub struct CompiledGoal {
+    record: Record,
+    arity: usize,
+}
+
+impl CompiledGoal {
+    /// The amount of arguments this goal has to be called with.
+    pub fn arity(&self) -> usize {
+        self.arity
+    }
+
+    /// Call the goal once, binding its free variables to the given arguments.
+    ///
+    /// Arguments are unified with the variables of the goal, so
+    /// unbound arguments can be used to retrieve results. On
+    /// success, these bindings remain in place. Only the first
+    /// solution of the goal is retrieved.
+    ///
+    /// This panics if the amount of arguments does not match the
+    /// arity of the goal.
+    pub fn call<C: QueryableContextType>(
+        &self,
+        context: &Context<C>,
+        args: &[&Term],
+    ) -> PrologResult<()> {
+        if args.len() != self.arity {
+            panic!(
+                "compiled goal has {} variables but was called with {} arguments",
+                self.arity,
+                args.len()
+            );
+        }
+
+        let frame = context.open_frame();
+        let [template, variables, goal] = frame.new_term_refs();
+        template.put(&self.record)?;
+        template.unify_arg(1, &variables)?;
+        template.unify_arg(2, &goal)?;
+        for (variable, arg) in frame.term_list_iter(&variables).zip(args) {
+            variable.unify(*arg)?;
         }
+
+        frame.call_once(crate::pred!(call / 1), [&goal])?;
+        frame.close();
+
+        Ok(())
     }
 }
 
@@ -263,20 +1140,40 @@ unsafe impl OpenCall for OpenQuery {
         }
     }
 
-    fn cut(mut this: Context<Self>) {
+    fn cut(mut this: Context<Self>) -> PrologResult<()> {
         this.assert_activated();
-        // TODO handle exceptions
-
-        unsafe { PL_cut_query(this.context.qid) };
+        let result = unsafe { PL_cut_query(this.context.qid) };
         this.context.closed = true;
+
+        close_result(result)
     }
 
-    fn discard(mut this: Context<Self>) {
+    fn discard(mut this: Context<Self>) -> PrologResult<()> {
         this.assert_activated();
-        // TODO handle exceptions
-
-        unsafe { PL_close_query(this.context.qid) };
+        let result = unsafe { PL_close_query(this.context.qid) };
         this.context.closed = true;
+
+        close_result(result)
+    }
+
+    fn arguments<'b>(this: &'b Context<Self>) -> Vec<Term<'b>> {
+        (0..this.context.arity)
+            .map(|i| unsafe { this.wrap_term_ref(this.context.args + i) })
+            .collect()
+    }
+}
+
+fn close_result(result: c_int) -> PrologResult<()> {
+    if result != 0 {
+        return Ok(());
+    }
+
+    // The query is gone at this point, so any exception raised while
+    // closing it has already been moved to the environment.
+    if unsafe { pl_default_exception() } == 0 {
+        Err(PrologError::Failure)
+    } else {
+        Err(PrologError::Exception)
     }
 }
 
@@ -296,13 +1193,37 @@ impl<const N: usize> Callable<N> for CallablePredicate<N> {
         context: &'a Context<C>,
         module: Option<Module>,
         args: [&Term; N],
+    ) -> Context<'a, Self::ContextType> {
+        self.open_with_options(context, module, QueryOptions::default(), args)
+    }
+
+    fn open_with_options<'a, C: ContextType>(
+        self,
+        context: &'a Context<C>,
+        module: Option<Module>,
+        options: QueryOptions,
+        args: [&Term; N],
     ) -> Context<'a, Self::ContextType> {
         context.assert_activated();
         context.assert_no_exception();
+        if cfg!(debug_assertions) {
+            // CallablePredicate::wrap does not check the arity, so a
+            // mismatch can only be caught here.
+            let predicate = unsafe { Predicate::wrap(self.predicate) };
+            let arity = predicate.arity();
+            if arity as usize != N {
+                panic!(
+                    "tried to call predicate {}/{} with {} arguments",
+                    predicate.name_string(),
+                    arity,
+                    N
+                );
+            }
+        }
         let module_context = module
             .map(|c| c.module_ptr())
             .unwrap_or(std::ptr::null_mut());
-        let flags = PL_Q_NORMAL | PL_Q_CATCH_EXCEPTION | PL_Q_EXT_STATUS;
+        let flags = options.flags();
         unsafe {
             let terms = PL_new_term_refs(N as i32);
             for (i, arg) in args.iter().enumerate() {
@@ -317,7 +1238,12 @@ impl<const N: usize> Callable<N> for CallablePredicate<N> {
                 terms,
             );
 
-            let query = OpenQuery { qid, closed: false };
+            let query = OpenQuery {
+                qid,
+                closed: false,
+                args: terms,
+                arity: N,
+            };
 
             context.deactivate();
             Context::new_activated(context, query, context.engine_ptr())
@@ -392,4 +1318,377 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn enumerate_solutions_framed() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let query = context.open(
+            pred!(between / 3),
+            [&term! {context: 1}?, &term! {context: 1000}?, &x],
+        );
+        let mut count = 0;
+        for (i, result) in query
+            .solutions_framed(|frame| {
+                // create some terms to be reclaimed after each solution
+                let copy = frame.new_term_ref();
+                copy.unify(&x)?;
+                copy.get::<u64>()
+            })
+            .enumerate()
+        {
+            assert_eq!(i as u64 + 1, result?);
+            count += 1;
+        }
+        query.cut()?;
+
+        assert_eq!(1000, count);
+
+        Ok(())
+    }
+
+    #[test]
+    fn cut_query_with_throwing_cleanup() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let goal =
+            context.term_from_string("setup_call_cleanup(true, member(_, [a, b]), throw(oops))")?;
+        let query = context.open_call(&goal);
+        assert!(query.next_solution()?);
+        assert!(query.cut().unwrap_err().is_exception());
+        assert!(context.has_exception());
+        context.clear_exception();
+
+        let query = context.open_call(&goal);
+        assert!(query.next_solution()?);
+        assert!(query.discard().unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn open_with_query_options() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let goal = term! {context: throw(oops)}?;
+        let options = QueryOptions::new().pass_exceptions(true);
+        let query = context.open_with_options(pred!(call / 1), None, options, [&goal]);
+        assert!(query.next_solution().unwrap_err().is_exception());
+        let _ = query.discard();
+        context.with_exception(|e| assert_eq!(atom!("oops"), e.unwrap().get::<Atom>().unwrap()));
+        context.clear_exception();
+
+        let x = context.new_term_ref();
+        let options = QueryOptions::new().debug(false);
+        let query = context.open_with_options(
+            pred!(between / 3),
+            None,
+            options,
+            [&term! {context: 1}?, &term! {context: 2}?, &x],
+        );
+        assert!(query.next_solution()?);
+        assert_eq!(1, x.get::<u64>()?);
+        query.cut()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn next_typed_rows() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let query = context.open(
+            pred!(between / 3),
+            [&term! {context: 1}?, &term! {context: 3}?, &x],
+        );
+        let mut values = Vec::new();
+        while let Some(row) = query.next::<(u64,)>() {
+            values.push(row?.0);
+        }
+        query.cut()?;
+        assert_eq!(vec![1, 2, 3], values);
+
+        let query = context.open(pred!(atom_length / 2), [&term! {context: abc}?, &x]);
+        assert!(query.next::<(Atom,)>().unwrap().unwrap_err().is_failure());
+        query.cut()?;
+
+        let query = context.open(pred!(atom_length / 2), [&term! {context: abc}?, &x]);
+        assert!(query
+            .next::<(Atom, u64, u64)>()
+            .unwrap()
+            .unwrap_err()
+            .is_exception());
+        assert!(query.discard().unwrap_err().is_exception());
+        let exception = context.take_exception().unwrap();
+        assert_eq!(
+            ExceptionKind::DomainError {
+                domain: "solution_row".to_string(),
+                culprit: "3".to_string()
+            },
+            context.decode_exception(&exception)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_from_string_bindings() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let query = context.query_from_string("between(1, 3, X), Y is X * 2, _ = z")?;
+        assert_eq!(vec!["X", "Y"], query.variable_names().collect::<Vec<_>>());
+        let mut rows = Vec::new();
+        for bindings in query {
+            let bindings = bindings?;
+            assert_eq!(2, bindings.len());
+            rows.push((bindings["X"].get::<u64>()?, bindings["Y"].get::<u64>()?));
+        }
+        assert_eq!(vec![(1, 2), (2, 4), (3, 6)], rows);
+
+        let mut query = context.query_from_string("X = 1 ; X = 2")?;
+        let first = query.next().unwrap()?;
+        assert_eq!(1, first["X"].get::<u64>()?);
+        query.cut()?;
+
+        assert!(matches!(
+            context.query_from_string("foo("),
+            Err(PrologError::Exception)
+        ));
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_solutions() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let goal = term! {context: member(#&x, [a, b, c])}?;
+        let query = context.open_call(&goal);
+        let mut atoms = Vec::new();
+        for solution in query.solutions() {
+            solution?;
+            atoms.push(x.get::<Atom>()?);
+        }
+        query.cut()?;
+        assert_eq!(vec![atom!("a"), atom!("b"), atom!("c")], atoms);
+
+        let goal = term! {context: atom_length(#&x, _)}?;
+        let query = context.open_call(&goal);
+        let results: Vec<_> = query.solutions().collect();
+        assert_eq!(1, results.len());
+        assert!(results[0].as_ref().unwrap_err().is_exception());
+        let _ = query.discard();
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn enumerate_solutions_framed_exception() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let query = context.open(pred!(atom_length / 2), [&x, &term! {context: _}?]);
+        let results: Vec<_> = query.solutions_framed(|_| Ok(())).collect();
+        assert_eq!(1, results.len());
+        assert!(results[0].as_ref().unwrap_err().is_exception());
+        assert!(query.discard().unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn generator_over_infinite_goal() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let goal = term! {context: between(1, inf, X)}?;
+        let out = context.new_term_ref();
+        goal.unify_arg(3, &out)?;
+
+        let mut generator = context.generator::<u64>(&goal, &out);
+        assert_eq!(1, generator.next().unwrap()?);
+        assert_eq!(2, generator.next().unwrap()?);
+        let rest: Vec<u64> = generator.take(3).collect::<PrologResult<_>>()?;
+        assert_eq!(vec![3, 4, 5], rest);
+
+        // the generator was dropped, so the context is usable again
+        let term = context.new_term_ref();
+        term.unify(42_u64)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn generator_ends_after_last_solution() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let goal = term! {context: member(X, [a, b, c])}?;
+        let out = context.new_term_ref();
+        goal.unify_arg(1, &out)?;
+
+        let results: Vec<Atom> = context
+            .generator(&goal, &out)
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(vec![atom!("a"), atom!("b"), atom!("c")], results);
+
+        Ok(())
+    }
+
+    #[test]
+    fn solve_goal_from_text() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let names: Vec<String> = context
+            .solve("member(X-Name, [1-\"one\", 2-\"two\"]), X > 1", "Name")?
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(vec!["two".to_string()], names);
+
+        let result = context.solve::<u64>("member(X, [1, 2)", "X");
+        assert!(result.err().unwrap().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn take_values_stops_after_n() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let goal = term! {context: member(#&x, [a, b, c])}?;
+        let first: Vec<Atom> = context.take_values(&goal, &x, 2)?;
+        assert_eq!(vec![atom!("a"), atom!("b")], first);
+        let all: Vec<Atom> = context.take_values(&goal, &x, 10)?;
+        assert_eq!(3, all.len());
+        assert!(context.take_values::<Atom>(&goal, &x, 0)?.is_empty());
+        assert!(x.is_var());
+
+        let goal = term! {context: member(#&x, [1, two])}?;
+        assert!(context
+            .take_values::<u64>(&goal, &x, 2)
+            .unwrap_err()
+            .is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn solve_without_variable_raises() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let result = context.solve::<u64>("member(X, [1, 2])", "Y");
+        assert!(result.err().unwrap().is_exception());
+        let exception = context.take_exception().unwrap();
+        assert_eq!(
+            ExceptionKind::ExistenceError {
+                kind: "variable".to_string(),
+                culprit: "'Y'".to_string(),
+            },
+            context.decode_exception(&exception)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_compiled_goal_repeatedly() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let template = term! {context: atom_length(_, _)}?;
+        let goal = context.compile_goal(&template)?;
+        assert_eq!(2, goal.arity());
+        // the template itself is left alone
+        assert!(template.get_arg::<Atom>(1).unwrap_err().is_failure());
+
+        for (word, expected) in [("a", 1), ("hello", 5), ("prolog", 6)] {
+            let length = context.new_term_ref();
+            goal.call(&context, &[&term! {context: #word}?, &length])?;
+            assert_eq!(expected, length.get::<u64>()?);
+        }
+
+        let length = context.new_term_ref();
+        length.unify(3_u64)?;
+        assert!(goal
+            .call(&context, &[&term! {context: hello}?, &length])
+            .unwrap_err()
+            .is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn collect_indexed_solutions() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let query = context.open(
+            pred!(between / 3),
+            [&term! {context: 1}?, &term! {context: 3}?, &x],
+        );
+        let solutions = query.collect_indexed(|_| x.get::<u64>())?;
+        query.cut()?;
+        let expected: Vec<_> = [(0, false, 1), (1, false, 2), (2, true, 3)]
+            .iter()
+            .map(|&(index, last, value)| IndexedSolution { index, last, value })
+            .collect();
+        assert_eq!(expected, solutions);
+
+        let goal = context.term_from_string("(X = a ; X = b ; fail)")?;
+        let query = context.open(pred!(call / 1), [&goal]);
+        let solutions = query.collect_indexed(|_| Ok(()))?;
+        query.cut()?;
+        assert_eq!(2, solutions.len());
+        assert!(solutions.iter().all(|s| !s.last));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "tried to call predicate atom_length/2 with 3 arguments")]
+    fn open_query_with_wrong_arity_panics() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let predicate = Predicate::new(Functor::new("atom_length", 2), Module::new("user"));
+        let callable: CallablePredicate<3> =
+            unsafe { CallablePredicate::wrap(predicate.predicate_ptr()) };
+        let [a, b, c] = context.new_term_refs();
+        let _query = context.open(callable, [&a, &b, &c]);
+    }
 }
@@ -57,18 +57,23 @@ use super::atom::*;
 use super::callable::*;
 use super::engine::*;
 use super::fli::*;
+use super::functor::*;
 use super::module::*;
 use super::result::*;
 use super::stream::*;
 use super::term::*;
 
+use lazy_static::*;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicU64, Ordering};
 use swipl_macros::pred;
 
-use swipl_macros::{prolog, term};
+use swipl_macros::{atom, functor, predicates, prolog, term};
 
 pub(crate) unsafe fn with_cleared_exception<R>(f: impl FnOnce() -> R) -> R {
     let error_term_ref = pl_default_exception();
@@ -196,6 +201,27 @@ impl<'a, T: ContextType> Context<'a, T> {
         self.engine
     }
 
+    /// Call the given function with an [ActiveToken], proving that this context's engine is active.
+    ///
+    /// This checks once that the context is active, and that its
+    /// engine is the one active on this thread. Hand-written wrappers
+    /// around `PL_*` functions that this library does not cover can
+    /// then require an `ActiveToken` as evidence, instead of relying
+    /// on their callers to keep track of this.
+    ///
+    /// The token is only valid for the duration of the call. It
+    /// cannot be stored beyond it, or sent to another thread.
+    pub fn with_active_engine<R>(&self, f: impl FnOnce(ActiveToken) -> R) -> R {
+        self.assert_activated();
+        if unsafe { current_engine_ptr() } != self.engine {
+            panic!("context engine is not the engine active on this thread");
+        }
+
+        f(ActiveToken {
+            _lifetime: PhantomData,
+        })
+    }
+
     /// Return the engine pointer as a `TermOrigin`, which is used in the construction of a `Term` in unsafe code.
     pub(crate) fn as_term_origin(&self) -> TermOrigin {
         unsafe { TermOrigin::new(self.engine_ptr()) }
@@ -301,6 +327,24 @@ impl<'a, T: ContextType> Context<'a, T> {
     }
 }
 
+/// A zero-sized proof that an engine is active on the current thread.
+///
+/// This is handed out by [Context::with_active_engine]. Code that
+/// calls into the SWI-Prolog foreign language interface directly can
+/// take this token to show that calling `PL_*` functions is allowed.
+#[derive(Clone, Copy)]
+pub struct ActiveToken<'a> {
+    // the raw pointer keeps the token from being sent to other threads
+    _lifetime: PhantomData<&'a *const ()>,
+}
+
+impl<'a> ActiveToken<'a> {
+    /// Returns the pointer of the engine that is known to be active.
+    pub fn engine_ptr(&self) -> PL_engine_t {
+        unsafe { current_engine_ptr() }
+    }
+}
+
 trait ContextParent {
     fn reactivate(&self);
 }
@@ -588,6 +632,56 @@ impl<'a, C: FrameableContextType> Context<'a, C> {
         self.activated.set(false);
         unsafe { Context::new_activated(self, frame, self.engine) }
     }
+
+    /// Run the given function in a new frame, closing the frame afterwards.
+    ///
+    /// This is a scoped alternative to pairing
+    /// [open_frame](Context::open_frame) with
+    /// [close](Context::close). Term refs created in the frame can't
+    /// escape the function, but any bindings it made to terms from
+    /// outside the frame are kept. If the function panics, the frame
+    /// is discarded instead.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let result = context.new_term_ref();
+    /// context.with_frame(|frame| {
+    ///     let [x, y] = frame.new_term_refs();
+    ///     x.unify(2_u64)?;
+    ///     frame.call_once(pred!(succ / 2), [&x, &y])?;
+    ///     result.unify(&y)
+    /// })?;
+    /// assert_eq!(3, result.get::<u64>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn with_frame<R, F: FnOnce(&Context<Frame>) -> R>(&self, f: F) -> R {
+        let frame = self.open_frame();
+        let result = f(&frame);
+        frame.close();
+
+        result
+    }
+
+    /// Run the given function in a new frame, discarding the frame afterwards.
+    ///
+    /// Unlike with [with_frame](Context::with_frame), any bindings
+    /// the function made are undone once it returns, even if it
+    /// succeeded. This is useful for checks and lookups which should
+    /// leave no trace, as long as the result is extracted into a rust
+    /// value before returning.
+    pub fn with_discarded_frame<R, F: FnOnce(&Context<Frame>) -> R>(&self, f: F) -> R {
+        let frame = self.open_frame();
+        let result = f(&frame);
+        frame.discard();
+
+        result
+    }
 }
 
 /// A trait marker for context types for which it is safe to open queries and create new term refs.
@@ -604,6 +698,23 @@ prolog! {
     fn open_call(term);
 }
 
+struct PrologMutexGuard<'b, 'a, T: QueryableContextType> {
+    context: &'b Context<'a, T>,
+    mutex: Term<'b>,
+}
+
+impl<'b, 'a, T: QueryableContextType> Drop for PrologMutexGuard<'b, 'a, T> {
+    fn drop(&mut self) {
+        // An exception may be pending, in which case prolog can't be
+        // called. with_exception temporarily clears it for us.
+        self.context.with_exception(|_| {
+            let _ = self
+                .context
+                .call_once(pred!(mutex_unlock / 1), [&self.mutex]);
+        });
+    }
+}
+
 impl<'a, T: QueryableContextType> Context<'a, T> {
     /// Create a new Term reference in the current context.
     ///
@@ -682,7 +793,7 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
     ///                             [&term!{context: "hello, ~q~n"}?,
     ///                              &term!{context: ["world"]}?]);
     ///    query.next_solution()?;
-    ///    query.cut();
+    ///    query.cut()?;
     /// #
     /// #  Ok(())
     /// # }
@@ -719,9 +830,7 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
     ) -> PrologResult<()> {
         let query = callable.open(self, None, args);
         query.next_solution()?;
-        query.cut();
-
-        Ok(())
+        query.cut()
     }
 
     /// Open a query, optionally passing in a context module.
@@ -734,157 +843,880 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
         callable.open(self, module, args)
     }
 
-    /// Turn the given string into a prolog term.
-    ///
-    /// This uses the prolog predicate `read_term_from_atom/3` for the
-    /// heavy lifting.
+    /// Open a query with the given options, optionally passing in a context module.
     ///
-    /// Consider using the `term!` macro instead.
-    pub fn term_from_string(&self, s: &str) -> PrologResult<Term> {
-        let term = self.new_term_ref();
-        let frame = self.open_frame();
+    /// See [QueryOptions] for the available options.
+    pub fn open_with_options<C: Callable<N>, const N: usize>(
+        &self,
+        callable: C,
+        module: Option<Module>,
+        options: QueryOptions,
+        args: [&Term; N],
+    ) -> Context<C::ContextType> {
+        callable.open_with_options(self, module, options, args)
+    }
 
-        let arg1 = frame.new_term_ref();
-        let arg3 = frame.new_term_ref();
+    /// Call a goal once in the context module of the foreign predicate that is currently running.
+    ///
+    /// The context module is retrieved with `PL_context()`. For most
+    /// predicates, this is simply the module the predicate was
+    /// defined in. For predicates that are module transparent, it is
+    /// instead the module that the predicate was called from. This
+    /// makes it possible to write meta-predicates in rust, which call
+    /// goals that were given to them the way the caller expects.
+    ///
+    /// Foreign predicates can be made module transparent after they
+    /// have been registered using `module_transparent/1`.
+    ///
+    /// Bindings made by the goal are kept, just like with
+    /// [call_once](Context::call_once).
+    pub fn call_in_caller_module(&self, goal: &Term) -> PrologResult<()> {
+        let module = unsafe { Module::wrap(PL_context()) };
+        let query = self.open_with_module(pred!(call / 1), Some(module), [goal]);
+        query.next_solution()?;
+        query.cut()
+    }
 
-        assert!(arg1.unify(s).is_ok());
-        assert!(arg3.unify(Nil).is_ok());
+    /// Call a goal which is expected to have at most one solution.
+    ///
+    /// If the goal succeeds deterministically, its bindings are kept
+    /// and `Ok(())` is returned. If it fails, this returns
+    /// `Err(PrologError::Failure)`. If it succeeds but leaves a
+    /// choicepoint behind, meaning there may be more solutions, this
+    /// is treated as a bug in the goal. The query is discarded, and
+    /// an exception of the form
+    /// `error(determinism_error(Goal, semidet, nondet, guard), _)` is
+    /// raised, which is what SWI-Prolog raises for `$/1` guards.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let goal = context.term_from_string("atom_length(hello, 5)")?;
+    /// context.call_semidet(&goal)?;
+    ///
+    /// let goal = context.term_from_string("member(_, [a, b])")?;
+    /// assert!(context.call_semidet(&goal).unwrap_err().is_exception());
+    /// #  context.clear_exception();
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn call_semidet(&self, goal: &Term) -> PrologResult<()> {
+        let query = self.open(pred!(call / 1), [goal]);
+        if !query.next_solution()? {
+            query.cut()?;
 
-        read_term_from_atom(&frame, &arg1, &term, &arg3).once()?;
-        frame.close();
+            return Ok(());
+        }
+        query.discard()?;
 
-        Ok(term)
+        let context = self;
+        let error = term! {context: error(determinism_error(#goal, semidet, nondet, guard), _)}?;
+        context.raise_exception(&error)
     }
 
-    /// Turn the given string into a prolog term.
+    /// Call a goal once as `Goal@Module`, making `module` the context module of the call.
     ///
-    /// This uses the prolog predicate `read_term_from_atom/3` for the
-    /// heavy lifting.
+    /// Prolog has two ways of involving a module in a call, which
+    /// are easily confused:
+    /// - `Module:Goal` changes where the predicate of the goal is
+    ///   looked up. It also makes `Module` the context module, but
+    ///   only if the predicate is module transparent.
+    /// - `Goal@Module` leaves the lookup alone, so the predicate is
+    ///   still found through any module qualification of the goal
+    ///   itself. It only sets the context module, which is what a
+    ///   module transparent predicate, such as a meta-predicate,
+    ///   uses to resolve the goals it calls in turn.
     ///
-    /// Consider using the `term!` macro instead.
-    pub fn string_from_term(&self, t: &Term) -> PrologResult<String> {
+    /// Bindings made by the goal are kept, just like with
+    /// [call_once](Context::call_once).
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// // context_module/1 is transparent, so it reports the context module.
+    /// let goal = context.term_from_string("lists:context_module(M)")?;
+    /// let m = context.new_term_ref();
+    /// goal.unify_arg(2, term! {context: context_module(#&m)}?)?;
+    ///
+    /// context.call_at_module(&goal, &Module::new("user"))?;
+    /// assert_eq!(atom!("user"), m.get::<Atom>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn call_at_module(&self, goal: &Term, module: &Module) -> PrologResult<()> {
         let frame = self.open_frame();
-        let out = frame.new_term_ref();
-
-        frame.call_once(pred!("term_string/2"), [t, &out])?;
-        let s: String = out.get()?;
+        let at = frame.new_term_ref();
+        at.unify(functor!("@/2"))?;
+        at.unify_arg(1, goal)?;
+        at.unify_arg(2, module.name())?;
+        frame.call_once(pred!(call / 1), [&at])?;
         frame.close();
 
-        Ok(s)
-    }
-
-    /// Open a query for the given term using the `call/1` prolog predicate.
-    pub fn open_call(&'a self, t: &Term<'a>) -> Context<'a, impl OpenCall> {
-        open_call(self, t)
-    }
-
-    pub fn call_term_once(&'a self, t: &Term<'a>) -> PrologResult<()> {
-        let open_call = self.open_call(t);
-        open_call.next_solution()?;
-        open_call.cut();
-
         Ok(())
     }
 
-    /// Turn a result into a `PrologResult`.
+    /// Run the given function while holding the named prolog mutex.
     ///
-    /// For this to work, the `Err` component of the `Result` needs to
-    /// implement the trait `IntoPrologException`. This is currently
-    /// only the case for [std::io::Error].
-    pub fn try_or_die<R, E: IntoPrologException>(&self, r: Result<R, E>) -> PrologResult<R> {
-        match r {
-            Ok(ok) => Ok(ok),
-            Err(e) => {
-                let reset_term = self.new_term_ref();
-                let exception_term = e.into_prolog_exception(self)?;
-                let result = self.raise_exception(&exception_term);
+    /// This uses `mutex_lock/1` and `mutex_unlock/1`, so it excludes
+    /// both other rust code using this function and prolog code using
+    /// `with_mutex/2` on the same mutex. As with `with_mutex/2`, the
+    /// mutex is created on first use. Mutexes are recursive, so it is
+    /// fine to lock the same mutex again from within the function.
+    ///
+    /// The function is called inside of a new frame, which is
+    /// closed afterwards, keeping any bindings it made. The mutex is
+    /// unlocked even if the function raises a prolog exception or
+    /// panics.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.with_mutex("counter", |context| {
+    ///     let fact = term! {context: counter(1)}?;
+    ///     context.call_once(pred!(assertz / 1), [&fact])
+    /// })?;
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn with_mutex<R, F: FnOnce(&Context<Frame>) -> R>(&self, name: &str, f: F) -> R {
+        let mutex = self.new_term_ref();
+        mutex
+            .unify(Atom::new(name))
+            .expect("unifying a fresh term ref with an atom should work");
+        self.call_once(pred!(mutex_lock / 1), [&mutex])
+            .expect("locking a mutex by name should work");
+        let _guard = PrologMutexGuard {
+            context: self,
+            mutex,
+        };
 
-                unsafe {
-                    reset_term.reset();
-                }
+        let frame = self.open_frame();
+        let result = f(&frame);
+        frame.close();
 
-                result
-            }
-        }
+        result
     }
 
-    /// Turn a result into a `PrologResult`.
+    /// Run the given grammar rule body over a list, using `phrase/2`.
     ///
-    /// For this to work, the `Err` component of the `Result` needs to
-    /// implement the trait [Error](std::error::Error).
-    pub fn try_or_die_generic<R, E: std::error::Error>(&self, r: Result<R, E>) -> PrologResult<R> {
-        match r {
-            Ok(ok) => Ok(ok),
-            Err(e) => {
-                let reset_term = self.new_term_ref();
-                let msg = format!("{}", e);
-
-                // TODO: term macro doesn't like self, which is
-                // probably only a problem for things inside this
-                // crate but still should probably be resolved.
-                let self_ = self;
-                let exception_term = term! {self_: error(rust_error(#msg), _)}?;
-                let result = self.raise_exception(&exception_term);
-
-                unsafe {
-                    reset_term.reset();
-                }
+    /// This succeeds if the whole list can be parsed (or generated)
+    /// by the grammar, and returns [PrologError::Failure]
+    /// otherwise. Only the first solution is retrieved.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let body = term! {context: [a, b]}?;
+    /// let list = term! {context: [a, b]}?;
+    /// context.phrase(&body, &list)?;
+    ///
+    /// let list = term! {context: [a, b, c]}?;
+    /// assert!(context.phrase(&body, &list).unwrap_err().is_failure());
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn phrase(&self, body: &Term, list: &Term) -> PrologResult<()> {
+        self.call_once(pred!(phrase / 2), [body, list])
+    }
 
-                result
-            }
-        }
+    /// Run the given grammar rule body over a list, using `phrase/3`.
+    ///
+    /// Unlike [phrase](Context::phrase), the list does not have to be
+    /// consumed completely. Whatever remains after parsing is
+    /// unified with `rest`. Only the first solution is retrieved.
+    pub fn phrase3(&self, body: &Term, list: &Term, rest: &Term) -> PrologResult<()> {
+        self.call_once(pred!(phrase / 3), [body, list, rest])
     }
 
-    /// Iterate over a term list.
+    /// Check that `action` succeeds for every solution of `cond`, using `forall/2`.
     ///
-    /// this returns a TermListIterator made out of the given
-    /// term. The TermListIterator will assume this is a cons cell,
-    /// and unify head and tail on each step of the iterator,
-    /// returning the head term and storing the tail term. If this
-    /// unification fails, the iterator stops.
+    /// This returns [PrologError::Failure] as soon as a solution of
+    /// `cond` is found for which `action` fails. Like `forall/2`,
+    /// this leaves no bindings behind.
     ///
-    /// Note that the terms created by this iterator are not
-    /// automatically thrown away. It is the caller's responsibility
-    /// to clean up terms if this is required, for example by using a
-    /// frame.
-    pub fn term_list_iter<'b>(&'b self, list: &Term) -> TermListIterator<'b, 'a, T> {
-        self.assert_activated();
-        let cur = self.new_term_ref();
-        cur.unify(list).expect("unifying terms should work");
-        TermListIterator { context: self, cur }
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let cond = context.term_from_string("member(X, [1, 2, 3])")?;
+    /// let x = context.new_term_ref();
+    /// cond.unify_arg(1, &x)?;
+    /// let action = term! {context: integer(#&x)}?;
+    /// context.forall(&cond, &action)?;
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn forall(&self, cond: &Term, action: &Term) -> PrologResult<()> {
+        self.call_once(pred!(forall / 2), [cond, action])
     }
 
-    /// Retrieve a term list as a fixed-size array.
+    /// Check that `goal` succeeds for every element of `list`, using `maplist/2`.
     ///
-    /// This is useful when a term contains a list whose supposed size
-    /// is known at compile time. If the actual list is larger than
-    /// this, only the first N elements are used. If the list is
-    /// smaller, the remaining terms in the array remain variables.
-    pub fn term_list_array<const N: usize>(&self, list: &Term) -> [Term; N] {
-        self.assert_activated();
-        // allocate these terms inside the scope of this context
-        let terms = self.new_term_refs();
-
-        let frame = self.open_frame();
-        let terms_iter = terms.iter();
-        let list_iter = frame.term_list_iter(list);
-
-        for (term, elt) in terms_iter.zip(list_iter) {
-            term.unify(elt).unwrap();
-        }
-        frame.close();
+    /// Bindings that the goal makes to the elements are kept. Only
+    /// the first solution is retrieved.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let goal = term! {context: integer}?;
+    /// context.maplist(&goal, &term! {context: [1, 2, 3]}?)?;
+    /// assert!(context
+    ///     .maplist(&goal, &term! {context: [1, two, 3]}?)
+    ///     .unwrap_err()
+    ///     .is_failure());
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn maplist(&self, goal: &Term, list: &Term) -> PrologResult<()> {
+        self.call_once(pred!(maplist / 2), [goal, list])
+    }
 
-        terms
+    /// Call `goal` on the corresponding elements of two lists, using `maplist/3`.
+    ///
+    /// This is most often used to map one list onto another, by
+    /// leaving `list2` unbound. Only the first solution is
+    /// retrieved.
+    pub fn maplist3(&self, goal: &Term, list1: &Term, list2: &Term) -> PrologResult<()> {
+        self.call_once(pred!(maplist / 3), [goal, list1, list2])
     }
 
-    /// Retrieve a term list as a Vec.
+    /// Call the given closure for every element of `list`, driven by `maplist/2`.
     ///
-    /// This will iterate over the given prolog list twice - once to
-    /// figure out its size, and then another time to actually
-    /// retrieve the elements. This is done so that we can allocate
-    /// the terms in a way that leaves no unused terms behind on the
-    /// stack (as would normally happen when iterating the list using
-    /// [term_list_iter](Context::term_list_iter)).
+    /// The closure is called with a context and the element. Like a
+    /// goal passed to [maplist](Context::maplist), it may bind the
+    /// element, and these bindings are kept. If the closure returns
+    /// an error, `maplist/2` stops there and the error is returned.
+    ///
+    /// Since the list is traversed by prolog, it doesn't need to be
+    /// a proper list. A partial list is extended with fresh
+    /// variables, just like `maplist/2` would do. Only the first
+    /// solution is retrieved, so a partial list is never extended
+    /// beyond its shortest possible length.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let list = term! {context: [3, 1, 2]}?;
+    /// let mut sum = 0;
+    /// context.maplist_with(&list, |_, element| {
+    ///     sum += element.get::<u64>()?;
+    ///     Ok(())
+    /// })?;
+    /// assert_eq!(6, sum);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn maplist_with<F>(&self, list: &Term, mut f: F) -> PrologResult<()>
+    where
+        F: FnMut(&Context<Frame>, &Term) -> PrologResult<()>,
+    {
+        if !*RUST_MAPLIST_CALL_REGISTERED {
+            return self.raise_registration_error("'$swipl_rs':'$rust_maplist_call'/2");
+        }
+
+        // the goal refers to the closure by an id rather than by
+        // address, so that prolog code holding on to the goal, or
+        // making up one of its own, can at worst get a failure.
+        let f: &mut MaplistClosure = &mut f;
+        // Safety: only the lifetime is erased. The registration
+        // removes the pointer from the table before the closure goes
+        // out of scope.
+        let f: *mut MaplistClosure<'static> = unsafe { std::mem::transmute(f) };
+        let id = NEXT_MAPLIST_CLOSURE_ID.fetch_add(1, Ordering::Relaxed);
+        MAPLIST_CLOSURES.with(|closures| closures.borrow_mut().insert(id, f));
+        let _registration = MaplistClosureRegistration(id);
+
+        let frame = self.open_frame();
+        let goal = frame.term_from_string(&format!("'$swipl_rs':'$rust_maplist_call'({})", id))?;
+        frame.call_once(pred!(maplist / 2), [&goal, list])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Collect the sorted set of solutions of a goal using `setof/3`.
+    ///
+    /// The variables in `existential` are existentially quantified,
+    /// as with `Vars^Goal`. If the goal has other free variables
+    /// that do not occur in the template, `setof/3` groups solutions
+    /// by their bindings. Only the first group is retrieved, with
+    /// those variables bound accordingly.
+    ///
+    /// Unlike `findall/3`, `setof/3` fails if there are no
+    /// solutions, in which case `None` is returned.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let [x, y] = context.new_term_refs();
+    /// let goal = term! {context: member(pair(#&x, #&y), [pair(b, 1), pair(a, 2), pair(b, 3)])}?;
+    /// let found = context.setof(&x, &[&y], &goal)?.unwrap();
+    /// let names: Vec<Atom> = found.iter().map(|t| t.get()).collect::<PrologResult<_>>()?;
+    /// assert_eq!(vec![atom!("a"), atom!("b")], names);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn setof(
+        &self,
+        template: &Term,
+        existential: &[&Term],
+        goal: &Term,
+    ) -> PrologResult<Option<Vec<Term>>> {
+        let [variables, quantified, list] = self.new_term_refs();
+        variables.unify(existential)?;
+        quantified.unify(functor!("^/2"))?;
+        quantified.unify_arg(1, &variables)?;
+        quantified.unify_arg(2, goal)?;
+
+        match self.call_once(pred!(setof / 3), [template, &quantified, &list]) {
+            Ok(()) => Ok(Some(self.term_list_iter(&list).collect())),
+            Err(PrologError::Failure) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Collect a copy of `template` for every solution of `goal`, stopping early once `cancel` is set.
+    ///
+    /// This is like `findall/3`, but the solutions are enumerated
+    /// from rust rather than inside prolog. In between solutions, the
+    /// cancel flag is checked. Once it is set, the query is cut and
+    /// the copies collected so far are returned. Note that the flag
+    /// is not looked at while prolog is busy finding the next
+    /// solution, so a single solution that takes very long still
+    /// can't be interrupted this way.
+    ///
+    /// When the solutions are to be processed one by one anyway,
+    /// iterating over an open query with a
+    /// [Generator](crate::callable::Generator) avoids collecting them
+    /// altogether.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # use std::sync::atomic::AtomicBool;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let x = context.new_term_ref();
+    /// let goal = term! {context: between(1, 3, #&x)}?;
+    /// let cancel = AtomicBool::new(false);
+    /// let found = context.findall_cancellable(&x, &goal, &cancel)?;
+    /// assert_eq!(3, found.len());
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn findall_cancellable(
+        &self,
+        template: &Term,
+        goal: &Term,
+        cancel: &std::sync::atomic::AtomicBool,
+    ) -> PrologResult<Vec<Term>> {
+        let mut records = Vec::new();
+        {
+            let query = self.open(pred!(call / 1), [goal]);
+            while !cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                match query.next_solution() {
+                    Ok(more) => {
+                        // bindings made by the goal are undone when
+                        // backtracking into it, so the solution is
+                        // recorded to get a copy that outlives this.
+                        records.push(template.record());
+                        if !more {
+                            break;
+                        }
+                    }
+                    Err(PrologError::Failure) => break,
+                    Err(e) => return Err(e),
+                }
+            }
+            query.discard()?;
+        }
+
+        let terms = self.new_term_refs_vec(records.len());
+        for (term, record) in terms.iter().zip(records.iter()) {
+            record.recorded(term)?;
+        }
+
+        Ok(terms)
+    }
+
+    /// Call a goal once using `call_with_depth_limit/3`.
+    ///
+    /// The recursion depth of the proof is not allowed to go beyond
+    /// `limit`. If the goal is proven within that limit, the maximum
+    /// depth that was reached is returned. If the goal fails, but hit
+    /// the limit along the way, [DepthResult::LimitExceeded] is
+    /// returned instead, as a deeper search might still find a
+    /// solution. If the goal fails without ever hitting the limit,
+    /// this returns `Err(PrologError::Failure)`.
+    ///
+    /// Bindings made by the goal are kept, just like with
+    /// [call_once](Context::call_once).
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let goal = context.term_from_string("length(L, 2)")?;
+    /// assert!(matches!(
+    ///     context.call_with_depth_limit(&goal, 10)?,
+    ///     DepthResult::Succeeded(_)
+    /// ));
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn call_with_depth_limit(&self, goal: &Term, limit: u64) -> PrologResult<DepthResult> {
+        let [limit_term, result] = self.new_term_refs();
+        limit_term.unify(limit)?;
+        self.call_once(
+            pred!(call_with_depth_limit / 3),
+            [goal, &limit_term, &result],
+        )?;
+
+        if result.get::<Atom>() == Ok(atom!("depth_limit_exceeded")) {
+            Ok(DepthResult::LimitExceeded)
+        } else {
+            Ok(DepthResult::Succeeded(result.get()?))
+        }
+    }
+
+    /// Call a goal using `call_residue_vars/2`, returning the variables that were left with constraints.
+    ///
+    /// When a goal uses a constraint library, such as `clpfd`, or
+    /// coroutining predicates like `freeze/2`, it may succeed while
+    /// leaving constraints on some of the variables it touched. The
+    /// answer then consists of both the bindings and these residual
+    /// constraints. This returns every variable that got attributes
+    /// during the call and still has them afterwards, including
+    /// variables that are not reachable from the goal itself. The
+    /// constraints can be inspected with `copy_term/3` or the
+    /// attribute predicates.
+    ///
+    /// Only the first solution is retrieved, and its bindings are
+    /// kept. If the goal fails, this returns
+    /// `Err(PrologError::Failure)`.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let x = context.new_term_ref();
+    /// let goal = term! {context: freeze(#&x, true)}?;
+    /// let residue = context.call_with_residue(&goal)?;
+    /// assert_eq!(1, residue.len());
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn call_with_residue(&self, goal: &Term) -> PrologResult<Vec<Term>> {
+        let vars = self.new_term_ref();
+        self.call_once(pred!(call_residue_vars / 2), [goal, &vars])?;
+
+        Ok(self.term_list_vec(&vars))
+    }
+
+    /// Compare two terms in the standard order of terms, using `compare/3`.
+    ///
+    /// The `Ord` implementation of [Term] compares terms directly
+    /// through the foreign language interface, which should give the
+    /// same result. This method instead goes through the prolog
+    /// predicate, for when it has to be certain that the ordering
+    /// is the one that `sort/2` and `@</2` use.
+    pub fn term_compare(&self, a: &Term, b: &Term) -> PrologResult<std::cmp::Ordering> {
+        let frame = self.open_frame();
+        let order = frame.new_term_ref();
+        frame.call_once(pred!(compare / 3), [&order, a, b])?;
+        let order: Atom = order.get()?;
+        frame.discard();
+
+        if order == atom!("<") {
+            Ok(std::cmp::Ordering::Less)
+        } else if order == atom!(">") {
+            Ok(std::cmp::Ordering::Greater)
+        } else {
+            Ok(std::cmp::Ordering::Equal)
+        }
+    }
+
+    /// Write a string to the current output stream using `write/1`.
+    ///
+    /// Unlike writing to [current_output](Context::current_output)
+    /// directly, this goes through prolog's own output machinery. In
+    /// particular, the position of the stream is kept up to date, so
+    /// that output from rust and prolog can be lined up.
+    pub fn write_str(&self, s: &str) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let text = frame.new_term_ref();
+        text.unify(s)?;
+        frame.call_once(pred!(write / 1), [&text])?;
+        frame.discard();
+
+        Ok(())
+    }
+
+    /// Write a newline to the current output stream using `nl/0`.
+    pub fn nl(&self) -> PrologResult<()> {
+        self.call_once(pred!(nl / 0), [])
+    }
+
+    /// Write `n` spaces to the current output stream using `tab/1`.
+    pub fn tab(&self, n: u64) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let count = frame.new_term_ref();
+        count.unify(n)?;
+        frame.call_once(pred!(tab / 1), [&count])?;
+        frame.discard();
+
+        Ok(())
+    }
+
+    /// Retrieve the position of the current output stream, as a
+    /// `(line, column)` pair.
+    ///
+    /// Lines are counted from 1, and columns from 0, following
+    /// `line_count/2` and `line_position/2`. This is useful to line
+    /// up output with text that was written by prolog, such as
+    /// through the column directives of `format/2`. If the current
+    /// output stream does not keep track of its position, `None` is
+    /// returned.
+    pub fn stream_position(&self) -> PrologResult<Option<(u64, u64)>> {
+        let frame = self.open_frame();
+        let [stream, property, position, line, column] = frame.new_term_refs();
+        frame.call_once(pred!(current_output / 1), [&stream])?;
+        property.unify(functor!("position/1"))?;
+        property.unify_arg(1, &position)?;
+        match frame.call_once(pred!(stream_property / 2), [&stream, &property]) {
+            Ok(()) => {}
+            Err(PrologError::Failure) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let line_count = term! {frame: line_count}?;
+        frame.call_once(
+            pred!(stream_position_data / 3),
+            [&line_count, &position, &line],
+        )?;
+        let line_position = term! {frame: line_position}?;
+        frame.call_once(
+            pred!(stream_position_data / 3),
+            [&line_position, &position, &column],
+        )?;
+        let result = (line.get()?, column.get()?);
+        frame.discard();
+
+        Ok(Some(result))
+    }
+
+    /// Turn the given string into a prolog term.
+    ///
+    /// This uses the prolog predicate `read_term_from_atom/3` for the
+    /// heavy lifting.
+    ///
+    /// Consider using the `term!` macro instead.
+    pub fn term_from_string(&self, s: &str) -> PrologResult<Term> {
+        let term = self.new_term_ref();
+        let frame = self.open_frame();
+
+        let arg1 = frame.new_term_ref();
+        let arg3 = frame.new_term_ref();
+
+        assert!(arg1.unify(s).is_ok());
+        assert!(arg3.unify(Nil).is_ok());
+
+        read_term_from_atom(&frame, &arg1, &term, &arg3).once()?;
+        frame.close();
+
+        Ok(term)
+    }
+
+    /// Turn the given string into a prolog term.
+    ///
+    /// This uses the prolog predicate `read_term_from_atom/3` for the
+    /// heavy lifting.
+    ///
+    /// Consider using the `term!` macro instead.
+    pub fn string_from_term(&self, t: &Term) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let out = frame.new_term_ref();
+
+        frame.call_once(pred!("term_string/2"), [t, &out])?;
+        let s: String = out.get()?;
+        frame.close();
+
+        Ok(s)
+    }
+
+    /// Concatenate the given atomic terms into a single atom, using `atomic_list_concat/2,3`.
+    ///
+    /// The parts may be atoms, strings or numbers. If a separator is
+    /// given, it is put in between every two parts.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let [a, b] = context.new_term_refs();
+    /// a.unify(atom!("key"))?;
+    /// b.unify(42_u64)?;
+    /// let joined = context.atomic_list_concat(&[&a, &b], Some("_"))?;
+    /// assert_eq!(atom!("key_42"), joined.get::<Atom>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn atomic_list_concat(
+        &self,
+        parts: &[&Term],
+        separator: Option<&str>,
+    ) -> PrologResult<Term> {
+        let [list, result] = self.new_term_refs();
+        list.unify(parts)?;
+        match separator {
+            Some(separator) => {
+                let separator_term = self.new_term_ref();
+                separator_term.unify(Atom::new(separator))?;
+                self.call_once(
+                    pred!(atomic_list_concat / 3),
+                    [&list, &separator_term, &result],
+                )?;
+            }
+            None => self.call_once(pred!(atomic_list_concat / 2), [&list, &result])?,
+        }
+
+        Ok(result)
+    }
+
+    /// Split an atom into the parts in between the occurrences of `separator`, using `atomic_list_concat/3`.
+    ///
+    /// This is the inverse of [atomic_list_concat](Context::atomic_list_concat).
+    /// Separators at the start or the end of the atom, or two
+    /// separators in a row, result in empty atoms. The separator
+    /// may not be empty, or `atomic_list_concat/3` will raise an
+    /// exception.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let parts = context.split_atom(&atom!("a,b,,c"), ",")?;
+    /// assert_eq!(vec![atom!("a"), atom!("b"), atom!(""), atom!("c")], parts);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn split_atom(&self, atom: &Atom, separator: &str) -> PrologResult<Vec<Atom>> {
+        let frame = self.open_frame();
+        let [list, separator_term, atom_term] = frame.new_term_refs();
+        separator_term.unify(Atom::new(separator))?;
+        atom_term.unify(atom)?;
+        frame.call_once(
+            pred!(atomic_list_concat / 3),
+            [&list, &separator_term, &atom_term],
+        )?;
+        let parts = list.get::<Vec<Atom>>()?;
+        frame.discard();
+
+        Ok(parts)
+    }
+
+    /// Open a query for the given term using the `call/1` prolog predicate.
+    pub fn open_call(&'a self, t: &Term<'a>) -> Context<'a, impl OpenCall> {
+        open_call(self, t)
+    }
+
+    pub fn call_term_once(&'a self, t: &Term<'a>) -> PrologResult<()> {
+        let open_call = self.open_call(t);
+        open_call.next_solution()?;
+        open_call.cut()
+    }
+
+    /// Turn a result into a `PrologResult`.
+    ///
+    /// For this to work, the `Err` component of the `Result` needs to
+    /// implement the trait `IntoPrologException`. This is currently
+    /// only the case for [std::io::Error].
+    pub fn try_or_die<R, E: IntoPrologException>(&self, r: Result<R, E>) -> PrologResult<R> {
+        match r {
+            Ok(ok) => Ok(ok),
+            Err(e) => {
+                let reset_term = self.new_term_ref();
+                let exception_term = e.into_prolog_exception(self)?;
+                let result = self.raise_exception(&exception_term);
+
+                unsafe {
+                    reset_term.reset();
+                }
+
+                result
+            }
+        }
+    }
+
+    /// Turn a result into a `PrologResult`.
+    ///
+    /// For this to work, the `Err` component of the `Result` needs to
+    /// implement the trait [Error](std::error::Error).
+    pub fn try_or_die_generic<R, E: std::error::Error>(&self, r: Result<R, E>) -> PrologResult<R> {
+        match r {
+            Ok(ok) => Ok(ok),
+            Err(e) => {
+                let reset_term = self.new_term_ref();
+                let msg = format!("{}", e);
+
+                // TODO: term macro doesn't like self, which is
+                // probably only a problem for things inside this
+                // crate but still should probably be resolved.
+                let self_ = self;
+                let exception_term = term! {self_: error(rust_error(#msg), _)}?;
+                let result = self.raise_exception(&exception_term);
+
+                unsafe {
+                    reset_term.reset();
+                }
+
+                result
+            }
+        }
+    }
+
+    /// Turn a result into an `Option`, raising a prolog exception on error.
+    ///
+    /// If the result is `Ok`, its value is returned in a `Some`. If
+    /// it is an `Err`, an exception `error(rust_error(Msg), _)` is
+    /// raised, where `Msg` is a string containing the error as
+    /// formatted through its [Display](std::fmt::Display)
+    /// implementation, and `None` is returned.
+    ///
+    /// This is meant for use from foreign predicates that call into
+    /// fallible rust code. On `None`, the predicate should return
+    /// without doing any further work, so that prolog can pick up
+    /// the exception.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// predicates! {
+    ///     semidet fn parse_integer(context, s, num) {
+    ///         let s: String = s.get_ex()?;
+    ///         match context.try_or_throw(s.parse::<u64>()) {
+    ///             Some(n) => num.unify(n),
+    ///             None => Err(PrologError::Exception),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn try_or_throw<R, E: std::fmt::Display>(&self, r: Result<R, E>) -> Option<R> {
+        match r {
+            Ok(ok) => Some(ok),
+            Err(e) => {
+                let reset_term = self.new_term_ref();
+                let msg = format!("{}", e);
+
+                let self_ = self;
+                if let Ok(exception_term) = term! {self_: error(rust_error(#msg), _)} {
+                    let _ = self.raise_exception::<()>(&exception_term);
+                }
+
+                unsafe {
+                    reset_term.reset();
+                }
+
+                None
+            }
+        }
+    }
+
+    /// Iterate over a term list.
+    ///
+    /// this returns a TermListIterator made out of the given
+    /// term. The TermListIterator will assume this is a cons cell,
+    /// and unify head and tail on each step of the iterator,
+    /// returning the head term and storing the tail term. If this
+    /// unification fails, the iterator stops.
+    ///
+    /// Note that the terms created by this iterator are not
+    /// automatically thrown away. It is the caller's responsibility
+    /// to clean up terms if this is required, for example by using a
+    /// frame.
+    pub fn term_list_iter<'b>(&'b self, list: &Term) -> TermListIterator<'b, 'a, T> {
+        self.assert_activated();
+        let cur = self.new_term_ref();
+        cur.unify(list).expect("unifying terms should work");
+        TermListIterator { context: self, cur }
+    }
+
+    /// Retrieve a term list as a fixed-size array.
+    ///
+    /// This is useful when a term contains a list whose supposed size
+    /// is known at compile time. If the actual list is larger than
+    /// this, only the first N elements are used. If the list is
+    /// smaller, the remaining terms in the array remain variables.
+    pub fn term_list_array<const N: usize>(&self, list: &Term) -> [Term; N] {
+        self.assert_activated();
+        // allocate these terms inside the scope of this context
+        let terms = self.new_term_refs();
+
+        let frame = self.open_frame();
+        let terms_iter = terms.iter();
+        let list_iter = frame.term_list_iter(list);
+
+        for (term, elt) in terms_iter.zip(list_iter) {
+            term.unify(elt).unwrap();
+        }
+        frame.close();
+
+        terms
+    }
+
+    /// Retrieve a term list as a Vec.
+    ///
+    /// This will iterate over the given prolog list twice - once to
+    /// figure out its size, and then another time to actually
+    /// retrieve the elements. This is done so that we can allocate
+    /// the terms in a way that leaves no unused terms behind on the
+    /// stack (as would normally happen when iterating the list using
+    /// [term_list_iter](Context::term_list_iter)).
     ///
     /// If you know in advance what the size is going to be (or you
     /// know a reasonable upper bound), consider using
@@ -1005,6 +1837,131 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
         Ok(terms)
     }
 
+    /// Take a compound term apart into its name and a Vec of its argument terms.
+    ///
+    /// The argument terms are allocated in this context, like with
+    /// [compound_terms_vec](Context::compound_terms_vec). If the given
+    /// term is not a compound, this method will fail. Note that this
+    /// includes atoms, even though prolog treats them as having
+    /// arity 0 in places like `functor/3`.
+    pub fn get_compound(&self, compound: &Term) -> PrologResult<(Atom, Vec<Term>)> {
+        let terms = self.compound_terms_vec(compound)?;
+        let functor: Functor = compound.get()?;
+
+        Ok((functor.name(), terms))
+    }
+
+    /// Build a compound term, calling the given closure for each of its arguments.
+    ///
+    /// A compound with the given name and arity is created in this
+    /// context. Then for each argument position, from 1 up to and
+    /// including the arity, the closure is called with that position,
+    /// and the argument is unified with whatever it returns. This is
+    /// useful when the arguments are computed, such as when they come
+    /// from the columns of a row of data.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let row = ["a", "b", "c"];
+    /// let term = context.build_compound_with("row", 3, |i| Atom::new(row[i as usize - 1]))?;
+    /// assert_eq!(atom!("c"), term.get_arg::<Atom>(3)?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn build_compound_with<U, F>(&self, name: &str, arity: u16, mut f: F) -> PrologResult<Term>
+    where
+        U: Unifiable,
+        F: FnMut(u16) -> U,
+    {
+        let term = self.new_term_ref();
+        term.unify(Functor::new(name, arity))?;
+        for i in 1..=arity {
+            term.unify_arg(i as usize, f(i))?;
+        }
+
+        Ok(term)
+    }
+
+    /// Build a conjunction out of the given goals.
+    ///
+    /// The result is a right-nested `(G1, (G2, (..., Gn)))` term,
+    /// which is what prolog itself reads `(G1, G2, ..., Gn)` as. When
+    /// given a single goal, the result is that goal. When given no
+    /// goals at all, the result is `true`.
+    pub fn conjoin(&self, goals: &[&Term]) -> Term {
+        self.assert_activated();
+        let result = self.new_term_ref();
+        let (last, init) = match goals.split_last() {
+            Some(split) => split,
+            None => {
+                result.put_val(atom!("true")).unwrap();
+                return result;
+            }
+        };
+
+        result.put(*last).unwrap();
+        let frame = self.open_frame();
+        for goal in init.iter().rev() {
+            let conjunction = frame.new_term_ref();
+            conjunction
+                .unify(functor!(",/2"))
+                .expect("unifying a fresh term with a functor should work");
+            conjunction.unify_arg(1, *goal).unwrap();
+            conjunction.unify_arg(2, &result).unwrap();
+            result.put(&conjunction).unwrap();
+        }
+        // closing rather than discarding, as discarding would get rid
+        // of the conjunctions we just built.
+        frame.close();
+
+        result
+    }
+
+    /// Split a conjunction into its conjuncts.
+    ///
+    /// This is the reverse of [conjoin](Context::conjoin). Nested
+    /// conjunctions are flattened, no matter how they are nested. A
+    /// term that is not a conjunction results in a single-element
+    /// Vec with just that term, except for `true`, which is
+    /// considered to be the empty conjunction and results in an
+    /// empty Vec.
+    ///
+    /// The returned terms are allocated in this context. Note that
+    /// some additional terms may be allocated for intermediate
+    /// conjunctions, which are not cleaned up until this context is.
+    pub fn get_conjunction(&self, term: &Term) -> Vec<Term> {
+        self.assert_activated();
+        let mut result = Vec::new();
+        if attempt_opt(term.get::<Atom>()).unwrap_or(None) == Some(atom!("true")) {
+            return result;
+        }
+
+        let start = self.new_term_ref();
+        start.unify(term).unwrap();
+        let mut stack = vec![start];
+        let conjunction = functor!(",/2");
+        while let Some(cur) = stack.pop() {
+            if attempt_opt(cur.get::<Functor>()).unwrap_or(None) == Some(conjunction) {
+                let [left, right] = self.new_term_refs();
+                unsafe {
+                    assert!(PL_get_arg(1, cur.term_ptr(), left.term_ptr()) == 1);
+                    assert!(PL_get_arg(2, cur.term_ptr(), right.term_ptr()) == 1);
+                }
+                stack.push(right);
+                stack.push(left);
+            } else {
+                result.push(cur);
+            }
+        }
+
+        result
+    }
+
     #[cfg(feature = "serde")]
     /// Deserialize a term into a rust value using serde.
     pub fn deserialize_from_term<'de, DT: Deserialize<'de>>(
@@ -1059,6 +2016,161 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
             _ => Ok((head, tail)),
         }
     }
+
+    /// Create a new difference list, returning the list and its tail.
+    ///
+    /// Initially, both the list and its tail are the same unbound
+    /// variable. Elements are added by unifying the tail with a new
+    /// cons cell, which can be done using
+    /// [difflist_push](Context::difflist_push) and
+    /// [difflist_extend](Context::difflist_extend). The list can be
+    /// closed at any point by unifying the tail with [Nil].
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let (list, tail) = context.new_difflist();
+    /// let tail = context.difflist_push(&tail, 1_u64)?;
+    /// let tail = context.difflist_extend(&tail, [2_u64, 3_u64])?;
+    /// tail.unify(Nil)?;
+    ///
+    /// assert_eq!(vec![1, 2, 3], list.get::<Vec<u64>>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn new_difflist(&self) -> (Term, Term) {
+        let [list, tail] = self.new_term_refs();
+        list.unify(&tail)
+            .expect("unifying two fresh variables should work");
+
+        (list, tail)
+    }
+
+    /// Add an element to the end of a difference list, returning the new tail.
+    ///
+    /// This unifies the given tail with `[Element|NewTail]`. The
+    /// old tail should no longer be used for adding elements after
+    /// this.
+    pub fn difflist_push<U: Unifiable>(&self, tail: &Term, element: U) -> PrologResult<Term> {
+        let (head, new_tail) = self.unify_list_functor(tail)?;
+        head.unify(element)?;
+
+        Ok(new_tail)
+    }
+
+    /// Add all elements from the given iterator to the end of a
+    /// difference list, returning the new tail.
+    ///
+    /// Note that this allocates two terms for each element, which
+    /// will not be cleaned up until this context is.
+    pub fn difflist_extend<U: Unifiable, I: IntoIterator<Item = U>>(
+        &self,
+        tail: &Term,
+        elements: I,
+    ) -> PrologResult<Term> {
+        let mut cur = self.new_term_ref();
+        cur.put(tail)?;
+        for element in elements {
+            cur = self.difflist_push(&cur, element)?;
+        }
+
+        Ok(cur)
+    }
+
+    /// Build a proper list out of all elements from the given iterator.
+    ///
+    /// The list is built front to back, by repeatedly unifying a
+    /// single tail term with a new cons cell. Unlike with
+    /// [difflist_extend](Context::difflist_extend), the number of
+    /// term references in use stays the same no matter how many
+    /// elements there are, and nothing recurses over the list. This
+    /// makes it suitable for lists of millions of elements, which
+    /// are then only limited by the size of the global stack.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let list = context.build_long_list(0..10_000_i64)?;
+    /// assert_eq!(10_000, list.get::<Vec<i64>>()?.len());
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn build_long_list<U: Unifiable, I: IntoIterator<Item = U>>(
+        &self,
+        elements: I,
+    ) -> PrologResult<Term> {
+        let [list, tail] = self.new_term_refs();
+        tail.put(&list)?;
+        for element in elements {
+            // any term references the element allocates while being
+            // unified are released again when this frame closes.
+            let frame = self.open_frame();
+            let head = frame.new_term_ref();
+            if unsafe { PL_unify_list(tail.term_ptr(), head.term_ptr(), tail.term_ptr()) } == 0 {
+                frame.close();
+                return match unsafe { pl_default_exception() != 0 } {
+                    true => Err(PrologError::Exception),
+                    false => Err(PrologError::Failure),
+                };
+            }
+            let result = head.unify(element);
+            frame.close();
+            result?;
+        }
+        tail.unify(Nil)?;
+        unsafe {
+            tail.reset();
+        }
+
+        Ok(list)
+    }
+
+    /// Split a partial list into its elements and the unbound variable at its end.
+    ///
+    /// This is the reverse of [new_difflist](Context::new_difflist)
+    /// and friends. It fails if the list is not a partial list, that
+    /// is, if its final tail is not an unbound variable. This
+    /// includes lists that have already been closed.
+    pub fn get_difflist(&self, list: &Term) -> PrologResult<(Vec<Term>, Term)> {
+        self.assert_activated();
+        let mut elements = Vec::new();
+        let mut cur = self.new_term_ref();
+        cur.put(list)?;
+        loop {
+            let [head, tail] = self.new_term_refs();
+            if unsafe { PL_get_list(cur.term_ptr(), head.term_ptr(), tail.term_ptr()) } == 0 {
+                unsafe {
+                    head.reset();
+                }
+                break;
+            }
+            elements.push(head);
+            cur = tail;
+        }
+
+        if cur.is_var() {
+            Ok((elements, cur))
+        } else {
+            Err(PrologError::Failure)
+        }
+    }
+}
+
+/// The outcome of [Context::call_with_depth_limit].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthResult {
+    /// The goal succeeded, reaching at most the given depth.
+    Succeeded(u64),
+    /// The goal hit the depth limit.
+    LimitExceeded,
 }
 
 /// An iterator over a term list.
@@ -1149,18 +2261,68 @@ pub unsafe fn prolog_catch_unwind<F: FnOnce() -> R + std::panic::UnwindSafe, R>(
                 },
             }
 
-            context.raise_exception::<()>(&error_term).unwrap_err();
-            Err(PrologError::Exception)
-        }
+            context.raise_exception::<()>(&error_term).unwrap_err();
+            Err(PrologError::Exception)
+        }
+    }
+}
+
+type MaplistClosure<'c> = dyn FnMut(&Context<Frame>, &Term) -> PrologResult<()> + 'c;
+
+lazy_static! {
+    static ref RUST_MAPLIST_CALL_REGISTERED: bool =
+        register_rust_maplist_call_in_module(Some("$swipl_rs"));
+}
+
+static NEXT_MAPLIST_CLOSURE_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // The closures of the maplist_with calls active on this thread.
+    // A pointer is only in here while the closure it points to is
+    // alive, and not while that closure is running.
+    static MAPLIST_CLOSURES: RefCell<HashMap<u64, *mut MaplistClosure<'static>>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Removes a closure from `MAPLIST_CLOSURES` when dropped, including on unwinding.
+struct MaplistClosureRegistration(u64);
+
+impl Drop for MaplistClosureRegistration {
+    fn drop(&mut self) {
+        MAPLIST_CLOSURES.with(|closures| closures.borrow_mut().remove(&self.0));
+    }
+}
+
+predicates! {
+    #[name("$rust_maplist_call")]
+    semidet fn rust_maplist_call(context, id, element) {
+        let id: u64 = id.get()?;
+        // taking the closure out of the table while it runs means a
+        // nested call with the same id fails rather than aliasing it.
+        let f = MAPLIST_CLOSURES.with(|closures| closures.borrow_mut().remove(&id));
+        let f = match f {
+            Some(f) => f,
+            None => return Err(PrologError::Failure),
+        };
+
+        let frame = context.open_frame();
+        // Safety: the pointer was in the table, so the maplist_with
+        // call it belongs to is still running further down this
+        // thread's stack.
+        let result = unsafe { (*f)(&frame, element) };
+        frame.close();
+        MAPLIST_CLOSURES.with(|closures| closures.borrow_mut().insert(id, f));
+
+        result
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::functor::*;
     use crate::predicate::*;
-    use crate::predicates;
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
 
     #[test]
     fn get_term_ref_on_fresh_engine() {
@@ -1284,7 +2446,7 @@ mod tests {
 
             assert!(!next);
             assert_eq!(42_u64, term1.get()?);
-            query.discard();
+            query.discard()?;
         }
 
         // after leaving the block, we have discarded
@@ -1318,7 +2480,7 @@ mod tests {
 
             assert!(!next);
             assert_eq!(42_u64, term1.get()?);
-            query.cut();
+            query.cut()?;
         }
 
         // a cut query leaves data intact
@@ -1394,7 +2556,7 @@ mod tests {
         term.unify_arg(1, &term_x)?;
         let query = context.open_call(&term);
         assert!(!query.next_solution()?);
-        query.cut();
+        query.cut()?;
 
         assert!(term_x.unify(42_u64).is_ok());
 
@@ -1414,6 +2576,26 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn try_or_throw_raises_rust_error() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert_eq!(Some(42), context.try_or_throw(Ok::<u64, String>(42)));
+        assert!(!context.has_exception());
+
+        let result = context.try_or_throw(Err::<u64, _>("it broke"));
+        assert!(result.is_none());
+        assert!(context.has_exception());
+        context.with_exception(|e| {
+            let expected = term! {context: error(rust_error("it broke"), _)}.unwrap();
+            assert!(e.unwrap().unify(&expected).is_ok());
+        });
+
+        context.clear_exception();
+    }
+
     prolog! {
         #[name("is")]
         fn prolog_arithmetic(term, e);
@@ -1432,7 +2614,7 @@ mod tests {
         let query = prolog_arithmetic(&context, &term1, &term2);
         assert!(query.next_solution().unwrap_err().is_exception());
         assert!(query.has_exception());
-        query.discard();
+        let _ = query.discard();
         let _query2 = prolog_arithmetic(&context, &term1, &term2);
     }
 
@@ -1464,6 +2646,96 @@ mod tests {
         Ok(())
     }
 
+    predicates! {
+        #[module("rust_meta")]
+        semidet fn call_in_caller(context, goal) {
+            context.call_in_caller_module(goal)
+        }
+    }
+
+    #[test]
+    fn call_semidet_rejects_choicepoints() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let goal = term! {context: atom_length(hello, #&x)}?;
+        context.call_semidet(&goal)?;
+        assert_eq!(5, x.get::<u64>()?);
+
+        let goal = term! {context: fail}?;
+        assert!(context.call_semidet(&goal).unwrap_err().is_failure());
+
+        let y = context.new_term_ref();
+        let goal = term! {context: between(1, 3, #&y)}?;
+        assert!(context.call_semidet(&goal).unwrap_err().is_exception());
+        assert!(y.is_var());
+        context.with_exception(|e| {
+            let e = e.expect("exception should be set");
+            let formal = context.new_term_ref();
+            e.unify_arg(1, &formal).unwrap();
+            assert_eq!(
+                Functor::new("determinism_error", 4),
+                formal.get::<Functor>().unwrap()
+            );
+        });
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_at_module_only_changes_context() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [m1, m2] = context.new_term_refs();
+        let qualified = context.term_from_string("lists:context_module(_)")?;
+        qualified.unify_arg(2, term! {context: context_module(#&m1)}?)?;
+        context.call_term_once(&qualified)?;
+        assert_eq!(atom!("lists"), m1.get::<Atom>()?);
+
+        let qualified = context.term_from_string("lists:context_module(_)")?;
+        qualified.unify_arg(2, term! {context: context_module(#&m2)}?)?;
+        context.call_at_module(&qualified, &Module::new("user"))?;
+        assert_eq!(atom!("user"), m2.get::<Atom>()?);
+
+        // the predicate is still looked up through the goal's own qualification
+        let lookup = context.term_from_string("lists:append([a], [b], [a, b])")?;
+        context.call_at_module(&lookup, &Module::new("some_empty_module"))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_goal_in_caller_module() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+
+        assert!(register_call_in_caller());
+
+        let context: Context<_> = activation.into();
+        let transparent = context.term_from_string("rust_meta:call_in_caller/1")?;
+        context.call_once(pred!(module_transparent / 1), [&transparent])?;
+        let fact = context.term_from_string("caller_module:only_in_caller(42)")?;
+        context.call_once(pred!(assertz / 1), [&fact])?;
+
+        let x = context.new_term_ref();
+        let goal = term! {context: only_in_caller(#&x)}?;
+        let query = context.open_with_module(
+            pred!("rust_meta:call_in_caller/1"),
+            Some(Module::new("caller_module")),
+            [&goal],
+        );
+        query.next_solution()?;
+        query.cut()?;
+        assert_eq!(42, x.get::<u64>()?);
+
+        Ok(())
+    }
+
     #[test]
     fn call_prolog_from_generated_rust_query_opener() {
         let engine = Engine::new();
@@ -1645,6 +2917,44 @@ mod tests {
         assert!(terms.is_none());
     }
 
+    #[test]
+    fn term_compound_name_and_args() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let compound = context.term_from_string("moo(5, foo)").unwrap();
+        let (name, terms) = context.get_compound(&compound).unwrap();
+        assert_eq!(Atom::new("moo"), name);
+        assert_eq!(2, terms.len());
+        assert_eq!(5, terms[0].get::<u64>().unwrap());
+        assert_eq!(Atom::new("foo"), terms[1].get::<Atom>().unwrap());
+
+        let atom = context.term_from_string("moo").unwrap();
+        assert!(attempt_opt(context.get_compound(&atom)).unwrap().is_none());
+    }
+
+    #[test]
+    fn build_compound_from_closure() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.build_compound_with("squares", 3, |i| (i * i) as u64)?;
+        assert_eq!(functor!("squares/3"), term.get::<Functor>()?);
+        let args: [Term; 3] = context.compound_terms(&term)?;
+        assert_eq!(1, args[0].get::<u64>()?);
+        assert_eq!(4, args[1].get::<u64>()?);
+        assert_eq!(9, args[2].get::<u64>()?);
+
+        let [x] = context.new_term_refs();
+        let term = context.build_compound_with("pair", 2, |_| &x)?;
+        x.unify(42_u64)?;
+        assert_eq!(42, term.get_arg::<u64>(2)?);
+
+        Ok(())
+    }
+
     #[test]
     fn term_compound_not_a_compound() {
         let engine = Engine::new();
@@ -1655,4 +2965,508 @@ mod tests {
         let terms: Option<[Term; 4]> = attempt_opt(context.compound_terms(&compound)).unwrap();
         assert!(terms.is_none());
     }
+
+    #[test]
+    fn conjoin_goals() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let goals = [
+            term! {context: foo}?,
+            term! {context: bar(1)}?,
+            term! {context: baz(1, 2)}?,
+        ];
+        let conjunction = context.conjoin(&[&goals[0], &goals[1], &goals[2]]);
+        assert_eq!(
+            "foo,bar(1),baz(1,2)",
+            context.string_from_term(&conjunction)?
+        );
+
+        let single = context.conjoin(&[&goals[0]]);
+        assert_eq!(Atom::new("foo"), single.get::<Atom>()?);
+
+        let empty = context.conjoin(&[]);
+        assert_eq!(Atom::new("true"), empty.get::<Atom>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_conjunction_flattens() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("((a, (b, c)), d)")?;
+        let conjuncts = context.get_conjunction(&term);
+        let names: Vec<Atom> = conjuncts
+            .iter()
+            .map(|t| t.get::<Atom>())
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(vec![atom!("a"), atom!("b"), atom!("c"), atom!("d")], names);
+
+        let single = context.term_from_string("foo(X, Y)")?;
+        let conjuncts = context.get_conjunction(&single);
+        assert_eq!(1, conjuncts.len());
+        assert_eq!(single, conjuncts[0]);
+
+        let truth = context.term_from_string("true")?;
+        assert!(context.get_conjunction(&truth).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn build_difflist() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let (list, tail) = context.new_difflist();
+        let tail = context.difflist_push(&tail, 1_u64)?;
+        let tail = context.difflist_extend(&tail, [2_u64, 3_u64])?;
+
+        let (elements, rest) = context.get_difflist(&list)?;
+        let values: Vec<u64> = elements
+            .iter()
+            .map(|t| t.get::<u64>())
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(vec![1, 2, 3], values);
+        assert_eq!(tail, rest);
+
+        tail.unify(Nil)?;
+        assert_eq!(vec![1, 2, 3], list.get::<Vec<u64>>()?);
+        assert!(context.get_difflist(&list).unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn build_a_million_element_list() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.build_long_list(0..1_000_000_i64)?;
+        let length = context.new_term_ref();
+        context.call_once(pred!(length / 2), [&list, &length])?;
+        assert_eq!(1_000_000, length.get::<u64>()?);
+
+        let values = list.get::<Vec<i64>>()?;
+        assert_eq!(1_000_000, values.len());
+        assert!(values.iter().enumerate().all(|(i, v)| i as i64 == *v));
+
+        let empty = context.build_long_list(Vec::<i64>::new())?;
+        assert!(empty.unify(Nil).is_ok());
+
+        Ok(())
+    }
+
+    fn assert_grammar_rule(
+        context: &Context<impl QueryableContextType>,
+        rule: &str,
+    ) -> PrologResult<()> {
+        let [rule_term, clause] = context.new_term_refs();
+        rule_term.put(&context.term_from_string(rule)?)?;
+        context.call_once(pred!(expand_term / 2), [&rule_term, &clause])?;
+        context.call_once(pred!(assertz / 1), [&clause])
+    }
+
+    #[test]
+    fn phrase_with_grammar() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert_grammar_rule(
+            &context,
+            "phrase_test_greeting --> [hello], phrase_test_name.",
+        )?;
+        assert_grammar_rule(&context, "phrase_test_name --> [world].")?;
+        assert_grammar_rule(&context, "phrase_test_name --> [prolog].")?;
+
+        let body = term! {context: phrase_test_greeting}?;
+        context.phrase(&body, &term! {context: [hello, prolog]}?)?;
+        assert!(context
+            .phrase(&body, &term! {context: [hello, rust]}?)
+            .unwrap_err()
+            .is_failure());
+        assert!(context
+            .phrase(&body, &term! {context: [hello, world, again]}?)
+            .unwrap_err()
+            .is_failure());
+
+        let rest = context.new_term_ref();
+        context.phrase3(&body, &term! {context: [hello, world, again]}?, &rest)?;
+        assert_eq!(vec![atom!("again")], rest.get::<Vec<Atom>>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_functions_in_scoped_frames() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [kept, undone] = context.new_term_refs();
+        context.with_frame(|frame| {
+            let inner = frame.new_term_ref();
+            inner.unify(42_u64)?;
+            kept.unify(&inner)
+        })?;
+        assert_eq!(42, kept.get::<u64>()?);
+
+        let value: u64 = context.with_discarded_frame(|frame| {
+            undone.unify(43_u64)?;
+            frame.call_once(pred!(succ / 2), [&undone, &frame.new_term_ref()])?;
+            undone.get()
+        })?;
+        assert_eq!(43, value);
+        assert!(undone.is_var());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            context.with_frame(|_| {
+                undone.unify(44_u64).unwrap();
+                panic!("oops");
+            })
+        }));
+        assert!(result.is_err());
+        assert!(undone.is_var());
+        undone.unify(45_u64)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn with_mutex_unlocks_after_exception() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let result: PrologResult<()> = context.with_mutex("rust_test_mutex", |context| {
+            let error = term! {context: error(some_error, _)}?;
+            context.raise_exception(&error)
+        });
+        assert!(result.unwrap_err().is_exception());
+        context.clear_exception();
+
+        // the mutex is free again, so another engine can take it
+        std::thread::spawn(|| {
+            let engine = Engine::new();
+            let activation = engine.activate();
+            let context: Context<_> = activation.into();
+            let mutex = term! {context: rust_test_mutex}.unwrap();
+            context
+                .call_once(pred!(mutex_trylock / 1), [&mutex])
+                .unwrap();
+            context
+                .call_once(pred!(mutex_unlock / 1), [&mutex])
+                .unwrap();
+        })
+        .join()
+        .unwrap();
+
+        let answer = context.with_mutex("rust_test_mutex", |_| 42);
+        assert_eq!(42, answer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn forall_checks_every_solution() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let cond = term! {context: between(1, 5, #&x)}?;
+        let small = context.term_from_string("_ < 10")?;
+        small.unify_arg(1, &x)?;
+        let odd = context.term_from_string("_ mod 2 =:= 1")?;
+        let lhs = context.new_term_ref();
+        odd.unify_arg(1, &lhs)?;
+        lhs.unify_arg(1, &x)?;
+
+        context.forall(&cond, &small)?;
+        assert!(context.forall(&cond, &odd).unwrap_err().is_failure());
+        assert!(x.is_var());
+
+        Ok(())
+    }
+
+    #[test]
+    fn join_and_split_atoms() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [a, b, c] = context.new_term_refs();
+        a.unify(atom!("x"))?;
+        b.unify("y")?;
+        c.unify(7_u64)?;
+        let joined = context.atomic_list_concat(&[&a, &b, &c], None)?;
+        assert_eq!(atom!("xy7"), joined.get::<Atom>()?);
+        let joined = context.atomic_list_concat(&[&a, &b, &c], Some(", "))?;
+        assert_eq!(atom!("x, y, 7"), joined.get::<Atom>()?);
+        let empty = context.atomic_list_concat(&[], Some("-"))?;
+        assert_eq!(atom!(""), empty.get::<Atom>()?);
+
+        let parts = context.split_atom(&joined.get::<Atom>()?, ", ")?;
+        assert_eq!(vec![atom!("x"), atom!("y"), atom!("7")], parts);
+        assert_eq!(
+            vec![atom!("no separator")],
+            context.split_atom(&atom!("no separator"), "/")?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn maplist_with_goals_and_closures() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = term! {context: [a, b, c]}?;
+        context.maplist(&term! {context: atom}?, &list)?;
+
+        let codes = context.new_term_ref();
+        context.maplist3(&term! {context: char_code}?, &list, &codes)?;
+        assert_eq!(
+            vec![97, 98, 99],
+            context
+                .term_list_vec(&codes)
+                .iter()
+                .map(|c| c.get::<u64>())
+                .collect::<PrologResult<Vec<_>>>()?
+        );
+
+        let mut seen = Vec::new();
+        context.maplist_with(&list, |_, element| {
+            seen.push(element.get::<Atom>()?);
+            Ok(())
+        })?;
+        assert_eq!(vec![atom!("a"), atom!("b"), atom!("c")], seen);
+
+        let unbound = term! {context: [_, _]}?;
+        context.maplist_with(&unbound, |_, element| element.unify(42_u64))?;
+        assert_eq!("[42,42]", context.string_from_term(&unbound)?);
+
+        let result = context.maplist_with(&list, |_, element| element.unify(atom!("b")));
+        assert!(result.unwrap_err().is_failure());
+
+        // the closure is only reachable while maplist_with runs
+        let stale = context.term_from_string("'$swipl_rs':'$rust_maplist_call'(0, a)")?;
+        assert!(context
+            .call_once(pred!(call / 1), [&stale])
+            .unwrap_err()
+            .is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn setof_with_and_without_quantification() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [x, y] = context.new_term_refs();
+        let goal = term! {context: member(pair(#&x, #&y), [pair(c, 2), pair(b, 1), pair(a, 2)])}?;
+        let atoms = |terms: Vec<Term>| -> PrologResult<Vec<Atom>> {
+            terms.iter().map(|t| t.get()).collect()
+        };
+
+        let all = context.setof(&x, &[&y], &goal)?.unwrap();
+        assert_eq!(vec![atom!("a"), atom!("b"), atom!("c")], atoms(all)?);
+        assert!(y.is_var());
+
+        let grouped = context.setof(&x, &[], &goal)?.unwrap();
+        assert_eq!(vec![atom!("b")], atoms(grouped)?);
+        assert_eq!(1, y.get::<u64>()?);
+
+        let none = term! {context: member(#&x, #Nil)}?;
+        assert!(context.setof(&x, &[], &none)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn findall_cancellable_stops_when_cancelled() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let goal = term! {context: between(1, 5, #&x)}?;
+        let template = term! {context: item(#&x)}?;
+        let cancel = AtomicBool::new(false);
+        let found = context.findall_cancellable(&template, &goal, &cancel)?;
+        let values: Vec<u64> = found
+            .iter()
+            .map(|t| t.get_arg::<u64>(1))
+            .collect::<PrologResult<_>>()?;
+        assert_eq!(vec![1, 2, 3, 4, 5], values);
+        assert!(x.is_var());
+
+        // an endless enumeration is cut short by cancelling from elsewhere
+        let endless = context.term_from_string("between(1, inf, _), sleep(0.01)")?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopper = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(200));
+                stop.store(true, std::sync::atomic::Ordering::Relaxed);
+            })
+        };
+        let found = context.findall_cancellable(&endless, &endless, &stop)?;
+        stopper.join().unwrap();
+        assert!(!found.is_empty());
+
+        cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        assert!(context
+            .findall_cancellable(&template, &goal, &cancel)?
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_with_residue_returns_constrained_variables() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let goal = term! {context: freeze(#&x, true)}?;
+        let residue = context.call_with_residue(&goal)?;
+        assert_eq!(1, residue.len());
+        assert!(residue[0].structurally_equal(&x));
+
+        // a binding that wakes the constraint leaves nothing behind
+        let woken = context.term_from_string("freeze(Y, true), Y = 1")?;
+        assert!(context.call_with_residue(&woken)?.is_empty());
+
+        let goal = term! {context: fail}?;
+        assert!(context.call_with_residue(&goal).unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn call_with_depth_limit_outcomes() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        for clause in [
+            "depth_countdown(0)",
+            "depth_countdown(N) :- N > 0, M is N - 1, depth_countdown(M)",
+        ] {
+            let clause = context.term_from_string(clause)?;
+            context.call_once(pred!(assertz / 1), [&clause])?;
+        }
+
+        let goal = term! {context: depth_countdown(5)}?;
+        match context.call_with_depth_limit(&goal, 100)? {
+            DepthResult::Succeeded(depth) => assert!((6..100).contains(&depth)),
+            DepthResult::LimitExceeded => panic!("depth limit unexpectedly exceeded"),
+        }
+        assert_eq!(
+            DepthResult::LimitExceeded,
+            context.call_with_depth_limit(&goal, 3)?
+        );
+
+        let goal = term! {context: fail}?;
+        assert_eq!(
+            Err(PrologError::Failure),
+            context.call_with_depth_limit(&goal, 100)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_to_current_output_and_get_position() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [file, stream, user_output] = context.new_term_refs();
+        context.call_once(
+            pred!(tmp_file_stream / 3),
+            [&term! {context: text}?, &file, &stream],
+        )?;
+        context.call_once(pred!(current_output / 1), [&user_output])?;
+        context.call_once(pred!(set_output / 1), [&stream])?;
+
+        let position = context.stream_position();
+        context.call_once(pred!(write / 1), [&term! {context: abc}?])?;
+        context.call_once(pred!(nl / 0), [])?;
+        context.call_once(pred!(write / 1), [&term! {context: de}?])?;
+        let next_position = context.stream_position();
+        context.nl()?;
+        context.tab(4)?;
+        context.write_str("h\u{e9}llo")?;
+        let written_position = context.stream_position();
+
+        context.call_once(pred!(set_output / 1), [&user_output])?;
+        context.call_once(pred!(close / 1), [&stream])?;
+        context.call_once(pred!(delete_file / 1), [&file])?;
+
+        assert_eq!(Some((1, 0)), position?);
+        assert_eq!(Some((2, 2)), next_position?);
+        assert_eq!(Some((3, 9)), written_position?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn term_compare_agrees_with_ord() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let terms = [
+            context.new_term_ref(),
+            term! {context: 1.5}?,
+            term! {context: 1}?,
+            term! {context: 2}?,
+            term! {context: foo}?,
+            term! {context: "foo"}?,
+            term! {context: foo(a)}?,
+            term! {context: foo(b)}?,
+            term! {context: bar(a, b)}?,
+        ];
+        for a in terms.iter() {
+            for b in terms.iter() {
+                assert_eq!(a.cmp(b), context.term_compare(a, b)?);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn active_token_for_raw_calls() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: foo(bar)}?;
+        let arity = context.with_active_engine(|token| {
+            assert_eq!(context.engine_ptr(), token.engine_ptr());
+            let mut arity = 0;
+            unsafe {
+                PL_get_compound_name_arity(term.term_ptr(), std::ptr::null_mut(), &mut arity);
+            }
+
+            arity
+        });
+        assert_eq!(1, arity);
+
+        Ok(())
+    }
 }
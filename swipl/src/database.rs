@@ -0,0 +1,646 @@
+//! Manipulation of the prolog clause database.
+//!
+//! This module contains helpers for adding and removing clauses at
+//! runtime, as you'd normally do with predicates like `assertz/1`
+//! and `retract/1`.
+use super::prelude::*;
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Add a clause to the end of the database using `assertz/1`.
+    ///
+    /// The clause may be a fact or a rule. If a module is given, the
+    /// clause is added to that module, otherwise it goes into the
+    /// module it is qualified with, or `user` if it is not qualified.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.assertz(None, &term! {context: color(red)}?)?;
+    /// context.asserta(None, &term! {context: color(green)}?)?;
+    ///
+    /// let color = context.new_term_ref();
+    /// context.call_once(pred!(color / 1), [&color])?;
+    /// assert_eq!(atom!("green"), color.get::<Atom>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn assertz(&self, module: Option<&str>, clause: &Term) -> PrologResult<()> {
+        self.modify_database(pred!(assertz / 1), module, clause)
+    }
+
+    /// Add a clause to the start of the database using `asserta/1`.
+    ///
+    /// See [assertz](Context::assertz) for how the module is determined.
+    pub fn asserta(&self, module: Option<&str>, clause: &Term) -> PrologResult<()> {
+        self.modify_database(pred!(asserta / 1), module, clause)
+    }
+
+    /// Remove the first clause unifying with the given clause using `retract/1`.
+    ///
+    /// This returns whether a clause was removed. On success, the
+    /// variables in the given clause are left bound to the removed
+    /// clause, so that retracting `counter(X)` also retrieves the
+    /// counter in `X`. See [assertz](Context::assertz) for how the
+    /// module is determined.
+    pub fn retract(&self, module: Option<&str>, clause: &Term) -> PrologResult<bool> {
+        match self.modify_database(pred!(retract / 1), module, clause) {
+            Ok(()) => Ok(true),
+            Err(PrologError::Failure) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn modify_database(
+        &self,
+        modifier: CallablePredicate<1>,
+        module: Option<&str>,
+        clause: &Term,
+    ) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let qualified = frame.new_term_ref();
+        match module {
+            Some(module) => {
+                qualified.unify(functor!(":/2"))?;
+                qualified.unify_arg(1, Atom::new(module))?;
+                qualified.unify_arg(2, clause)?;
+            }
+            None => qualified.unify(clause)?,
+        }
+        frame.call_once(modifier, [&qualified])?;
+        // closing keeps any bindings made by retract.
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Add a clause to the end of the database, recording the given
+    /// source location for it.
+    ///
+    /// The clause may be a fact or a rule, and may be module
+    /// qualified. The predicate it belongs to is declared dynamic, so
+    /// the clause can later be removed using `retract/1`, just like a
+    /// clause added with `assertz/1`.
+    ///
+    /// Unlike with `assertz/1`, the resulting clause will report
+    /// `file(File)` and `line_count(Line)` through
+    /// `clause_property/2`, making it show up in tools like
+    /// `listing/1` as originating from that location. This is
+    /// useful when building a knowledge base out of some external
+    /// data, allowing error messages to point back at the origin of
+    /// the data.
+    ///
+    /// There is no public SWI-Prolog API for this, so the clause is
+    /// added through `system:'$record_clause'/3`, the undocumented
+    /// predicate the compiler uses for clauses read from a file.
+    /// It may change or go away in a future SWI-Prolog version. It
+    /// also means the clause is owned by the given file, just as if
+    /// it had been loaded from there. When that file is loaded
+    /// again, for example by `consult/1` or by `make/0` after the
+    /// file changed on disk, SWI-Prolog wipes the clauses that came
+    /// from it, including the ones added here. Use a file name that
+    /// prolog will never load if the clauses have to stay.
+    pub fn assertz_with_source(&self, clause: &Term, file: &str, line: u32) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [module, plain, head, indicator, source] = frame.new_term_refs();
+
+        frame.call_once(pred!(strip_module / 3), [clause, &module, &plain])?;
+        if plain.get::<Functor>()? == functor!(":-/2") {
+            plain.unify_arg(1, &head)?;
+        } else {
+            head.unify(&plain)?;
+        }
+
+        let head_functor: Functor = head.get()?;
+        indicator.unify(functor!(":/2"))?;
+        indicator.unify_arg(1, &module)?;
+        let name_arity = frame.new_term_ref();
+        name_arity.unify(functor!("//2"))?;
+        name_arity.unify_arg(1, head_functor.name())?;
+        name_arity.unify_arg(2, head_functor.arity() as u64)?;
+        indicator.unify_arg(2, &name_arity)?;
+        frame.call_once(pred!(dynamic / 1), [&indicator])?;
+
+        let file_atom = Atom::new(file);
+        source.unify(functor!(":/2"))?;
+        source.unify_arg(1, &file_atom)?;
+        source.unify_arg(2, line as u64)?;
+        let file_term = frame.new_term_ref();
+        file_term.unify(&file_atom)?;
+        frame.call_once(
+            pred!("system:$record_clause/3"),
+            [clause, &file_term, &source],
+        )?;
+
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Add a fact to the end of the database for each of the given rows.
+    ///
+    /// Each row provides the arguments of a single fact, so that for
+    /// a `parent/2` table, you'd pass in an iterator of `(&str, &str)`
+    /// tuples. The arity of the facts is determined by the row
+    /// type. See [FactRow] for the supported row types. Note that
+    /// `&str` elements end up as prolog strings, so use [Atom]
+    /// elements where atoms are needed.
+    ///
+    /// All facts are built first, and then asserted in a single
+    /// call, using `maplist(assertz, Facts)` inside `transaction/1`.
+    /// This is a lot faster than asserting each fact with its own
+    /// query, and it makes the whole load atomic. If a row fails to
+    /// unify, or asserting a fact raises an exception, none of the
+    /// facts end up in the database. Other threads only see the new
+    /// facts once all of them are in. Since the facts all live on
+    /// the prolog stack at once, very large loads may need to be
+    /// split up into several calls.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.assert_facts("parent", [("alice", "bob"), ("bob", "carol")])?;
+    ///
+    /// let child = context.new_term_ref();
+    /// context.call_once(pred!(parent / 2), [&term! {context: "bob"}?, &child])?;
+    /// assert_eq!("carol", child.get::<String>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn assert_facts<I, R>(&self, name: &str, rows: I) -> PrologResult<()>
+    where
+        I: IntoIterator<Item = R>,
+        R: FactRow,
+    {
+        let functor = Functor::new(name, R::ARITY);
+        let frame = self.open_frame();
+        let [facts, tail] = frame.new_term_refs();
+        tail.put(&facts)?;
+        for row in rows {
+            // the term refs made for a row are released again when
+            // its frame closes. The list itself keeps the fact.
+            let row_frame = frame.open_frame();
+            let (fact, rest) = tail.unify_list_cell()?;
+            fact.unify(functor)?;
+            row.unify_args(&fact)?;
+            tail.put(&rest)?;
+            row_frame.close();
+        }
+        tail.unify(Nil)?;
+
+        let goal = term! {frame: maplist(assertz, #&facts)}?;
+        frame.call_once(pred!(transaction / 1), [&goal])?;
+        frame.discard();
+
+        Ok(())
+    }
+
+    /// Format a clause the way `portray_clause/1` would print it,
+    /// returning the result as a string.
+    ///
+    /// The resulting text uses standard operator syntax, names
+    /// variables `A`, `B`, etc., and ends with a full stop and a
+    /// newline. It can be read back by the prolog reader. This
+    /// makes it suitable for writing clauses that were built at
+    /// runtime back into a source file, for example after
+    /// enumerating them using `clause/2`.
+    pub fn portray_clause_to_string(&self, clause: &Term) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let [output, goal, string] = frame.new_term_refs();
+        output.unify(functor!("string/1"))?;
+        output.unify_arg(1, &string)?;
+        goal.unify(functor!("portray_clause/1"))?;
+        goal.unify_arg(1, clause)?;
+
+        frame.call_once(pred!(with_output_to / 2), [&output, &goal])?;
+        let result = string.get::<String>()?;
+        frame.discard();
+
+        Ok(result)
+    }
+
+    /// Enumerate the clauses matching the given head, as `(Head,
+    /// Body)` pairs.
+    ///
+    /// This is the equivalent of backtracking over `clause/2`. The
+    /// body of a fact is `true`. The matching clauses are determined
+    /// up front, so asserting or retracting clauses while iterating
+    /// does not change which clauses are visited. A clause that was
+    /// retracted in the meantime is returned as an
+    /// `Err(PrologError::Failure)`.
+    ///
+    /// Each pair is a fresh copy of the clause, created in this
+    /// context as it is yielded. The head template itself is not
+    /// bound. As with [term_list_iter](Context::term_list_iter), it is
+    /// up to the caller to clean up these terms if needed, for
+    /// example by using a frame.
+    ///
+    /// An exception is returned if the clauses cannot be looked up,
+    /// for example because the head is not callable, or the
+    /// predicate is not accessible through `clause/2`.
+    pub fn clauses<'b>(&'b self, head: &Term) -> PrologResult<ClauseIterator<'b, 'a, T>> {
+        let [goal, clause_ref, refs] = self.new_term_refs();
+        goal.unify(functor!("clause/3"))?;
+        goal.unify_arg(1, head)?;
+        goal.unify_arg(3, &clause_ref)?;
+        self.call_once(pred!(findall / 3), [&clause_ref, &goal, &refs])?;
+
+        Ok(ClauseIterator {
+            context: self,
+            refs: self.term_list_vec(&refs).into_iter(),
+        })
+    }
+
+    /// Retrieve all clauses of the given predicate, as `(Head, Body)` pairs.
+    ///
+    /// This collects the result of [clauses](Context::clauses) for a
+    /// head covering the entire predicate, so that the clauses can be
+    /// processed or rendered at leisure. If any clause could not be
+    /// retrieved, the first such error is returned instead.
+    pub fn list_predicate(&self, predicate: &Predicate) -> PrologResult<Vec<(Term, Term)>> {
+        let [head, plain] = self.new_term_refs();
+        plain.unify(Functor::new(predicate.name(), predicate.arity()))?;
+        head.unify(functor!(":/2"))?;
+        head.unify_arg(1, predicate.module().name())?;
+        head.unify_arg(2, &plain)?;
+
+        self.clauses(&head)?.collect()
+    }
+
+    /// Check whether a predicate with the given name and arity is defined.
+    ///
+    /// The name may be module qualified, as in `"lists:member"`. An
+    /// unqualified name is looked up in the `user` module. The check
+    /// is done with `current_predicate/1`, which never raises an
+    /// existence error, and does not autoload the predicate. This
+    /// makes it suitable as a guard before calling a predicate that
+    /// may not have been loaded.
+    pub fn predicate_exists(&self, name: &str, arity: u16) -> PrologResult<bool> {
+        let (module, name) = name.split_once(':').unwrap_or(("user", name));
+
+        let frame = self.open_frame();
+        let [indicator, name_arity] = frame.new_term_refs();
+        name_arity.unify(functor!("//2"))?;
+        name_arity.unify_arg(1, Atom::new(name))?;
+        name_arity.unify_arg(2, arity as u64)?;
+        indicator.unify(functor!(":/2"))?;
+        indicator.unify_arg(1, Atom::new(module))?;
+        indicator.unify_arg(2, &name_arity)?;
+
+        let result = match frame.call_once(pred!(current_predicate / 1), [&indicator]) {
+            Ok(()) => Ok(true),
+            Err(PrologError::Failure) => Ok(false),
+            Err(e) => Err(e),
+        };
+        frame.discard();
+
+        result
+    }
+
+    /// Add a clause to the end of the database, unless an identical clause is already there.
+    ///
+    /// Clauses are considered identical if they are variants of each
+    /// other, which means they are equal up to the naming of their
+    /// variables. This returns whether the clause was added.
+    ///
+    /// The check and the assert happen while holding a prolog mutex,
+    /// so that concurrent calls from different engines cannot both
+    /// add the same clause. Note that this only protects against
+    /// other callers of `assert_unique`. Code that uses `assertz/1`
+    /// directly can still introduce duplicates.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let fact = term! {context: seen(hello)}?;
+    /// assert!(context.assert_unique(&fact)?);
+    /// assert!(!context.assert_unique(&fact)?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn assert_unique(&self, clause: &Term) -> PrologResult<bool> {
+        self.with_mutex("$swipl_rs_assert_unique", |frame| {
+            let [module, plain, head, body] = frame.new_term_refs();
+            frame.call_once(pred!(strip_module / 3), [clause, &module, &plain])?;
+            if plain.get::<Functor>() == Ok(functor!(":-/2")) {
+                plain.unify_arg(1, &head)?;
+                plain.unify_arg(2, &body)?;
+            } else {
+                head.unify(&plain)?;
+                body.unify(atom!("true"))?;
+            }
+
+            // look for an existing clause of the same predicate which
+            // is a variant of the new one.
+            let head_functor: Functor = head.get()?;
+            let [existing_head, existing_body, qualified, found, pattern] = frame.new_term_refs();
+            existing_head.unify(head_functor)?;
+            qualified.unify(functor!(":/2"))?;
+            qualified.unify_arg(1, &module)?;
+            qualified.unify_arg(2, &existing_head)?;
+            found.unify(functor!("-/2"))?;
+            found.unify_arg(1, &existing_head)?;
+            found.unify_arg(2, &existing_body)?;
+            pattern.unify(functor!("-/2"))?;
+            pattern.unify_arg(1, &head)?;
+            pattern.unify_arg(2, &body)?;
+
+            let [lookup, variant] = frame.new_term_refs();
+            lookup.unify(functor!("clause/2"))?;
+            lookup.unify_arg(1, &qualified)?;
+            lookup.unify_arg(2, &existing_body)?;
+            variant.unify(functor!("=@=/2"))?;
+            variant.unify_arg(1, &found)?;
+            variant.unify_arg(2, &pattern)?;
+            let goal = frame.conjoin(&[&lookup, &variant]);
+
+            match frame.call_term_once(&goal) {
+                Ok(()) => return Ok(false),
+                Err(PrologError::Failure) => {}
+                Err(e) => return Err(e),
+            }
+
+            frame.call_once(pred!(assertz / 1), [clause])?;
+
+            Ok(true)
+        })
+    }
+}
+
+/// An iterator over the clauses of a predicate.
+///
+/// See [Context::clauses] for more information.
+pub struct ClauseIterator<'a, 'b, T: QueryableContextType> {
+    context: &'a Context<'b, T>,
+    refs: std::vec::IntoIter<Term<'a>>,
+}
+
+impl<'a, 'b, T: QueryableContextType> Iterator for ClauseIterator<'a, 'b, T> {
+    type Item = PrologResult<(Term<'a>, Term<'a>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let clause_ref = self.refs.next()?;
+        let [head, body] = self.context.new_term_refs();
+        let result = self
+            .context
+            .call_once(pred!(clause / 3), [&head, &body, &clause_ref]);
+
+        Some(result.map(|_| (head, body)))
+    }
+}
+
+/// A row of arguments for a fact, as used by [Context::assert_facts].
+///
+/// This is implemented for tuples of up to 8 unifiable elements.
+pub trait FactRow {
+    /// The arity of the facts built from this row.
+    const ARITY: u16;
+
+    /// Unify each element of this row with the corresponding argument of the given compound term.
+    fn unify_args(&self, term: &Term) -> PrologResult<()>;
+}
+
+macro_rules! fact_row_tuple {
+    ($arity:literal; $($t:ident $i:tt),*) => {
+        impl<$($t: Unifiable),*> FactRow for ($($t,)*) {
+            const ARITY: u16 = $arity;
+
+            fn unify_args(&self, term: &Term) -> PrologResult<()> {
+                $(term.unify_arg($i + 1, &self.$i)?;)*
+
+                Ok(())
+            }
+        }
+    };
+}
+
+fact_row_tuple!(1; A 0);
+fact_row_tuple!(2; A 0, B 1);
+fact_row_tuple!(3; A 0, B 1, C 2);
+fact_row_tuple!(4; A 0, B 1, C 2, D 3);
+fact_row_tuple!(5; A 0, B 1, C 2, D 3, E 4);
+fact_row_tuple!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+fact_row_tuple!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+fact_row_tuple!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assertz_with_source_records_location() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let clause = term! {context: sourced_fact(42)}?;
+        context.assertz_with_source(&clause, "/data/facts.csv", 12)?;
+
+        let [head, clause_ref, file, line] = context.new_term_refs();
+        head.unify(term! {context: sourced_fact(_)}?)?;
+        context.call_once(
+            pred!(clause / 3),
+            [&head, &term! {context: true}?, &clause_ref],
+        )?;
+        assert_eq!(42, head.get_arg::<u64>(1)?);
+
+        context.call_once(
+            pred!(clause_property / 2),
+            [&clause_ref, &term! {context: file(#&file)}?],
+        )?;
+        assert_eq!(atom!("/data/facts.csv"), file.get::<Atom>()?);
+
+        context.call_once(
+            pred!(clause_property / 2),
+            [&clause_ref, &term! {context: line_count(#&line)}?],
+        )?;
+        assert_eq!(12, line.get::<u64>()?);
+
+        context.call_once(pred!(retract / 1), [&clause])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn assert_facts_from_rows() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let rows = vec![
+            (atom!("alice"), "bob", 42_u64),
+            (atom!("bob"), "carol", 12_u64),
+        ];
+        context.assert_facts("facts_row", rows)?;
+
+        let [name, age] = context.new_term_refs();
+        context.call_once(pred!(facts_row / 3), [&term! {context: bob}?, &name, &age])?;
+        assert_eq!("carol", name.get::<String>()?);
+        assert_eq!(12, age.get::<u64>()?);
+
+        let count = context.new_term_ref();
+        context.call_once(
+            pred!(aggregate_all / 3),
+            [
+                &term! {context: count}?,
+                &term! {context: facts_row(_, _, _)}?,
+                &count,
+            ],
+        )?;
+        assert_eq!(2, count.get::<u64>()?);
+
+        // facts of a static predicate can't be asserted
+        let error = context.assert_facts("atom_length", [(atom!("a"), 1_u64)]);
+        assert!(error.unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
+    #[test]
+    fn portray_clause_as_string() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let fact = term! {context: person(alice, "Alice", 42)}?;
+        assert_eq!(
+            "person(alice, \"Alice\", 42).\n",
+            context.portray_clause_to_string(&fact)?
+        );
+
+        let rule = context.term_from_string("grandparent(X, Z) :- parent(X, Y), parent(Y, Z)")?;
+        assert_eq!(
+            "grandparent(A, B) :-\n    parent(A, C),\n    parent(C, B).\n",
+            context.portray_clause_to_string(&rule)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn enumerate_clauses() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        context.assert_facts("clause_color", [(atom!("red"),), (atom!("green"),)])?;
+        let rule = context.term_from_string("clause_color(X) :- X = blue")?;
+        context.call_once(pred!(assertz / 1), [&rule])?;
+
+        let template = term! {context: clause_color(_)}?;
+        let clauses = context
+            .clauses(&template)?
+            .collect::<PrologResult<Vec<_>>>()?;
+        assert_eq!(3, clauses.len());
+
+        let (head, body) = &clauses[0];
+        assert_eq!(atom!("red"), head.get_arg::<Atom>(1)?);
+        assert_eq!(atom!("true"), body.get::<Atom>()?);
+        let (head, body) = &clauses[1];
+        assert_eq!(atom!("green"), head.get_arg::<Atom>(1)?);
+        assert_eq!(atom!("true"), body.get::<Atom>()?);
+
+        let (head, body) = &clauses[2];
+        assert_eq!(functor!("=/2"), body.get::<Functor>()?);
+        assert_eq!(atom!("blue"), body.get_arg::<Atom>(2)?);
+        let [head_arg, body_arg] = context.new_term_refs();
+        head.unify_arg(1, &head_arg)?;
+        body.unify_arg(1, &body_arg)?;
+        assert_eq!(head_arg, body_arg);
+        assert!(head_arg.is_var());
+
+        Ok(())
+    }
+
+    #[test]
+    fn list_all_clauses_of_predicate() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        context.assert_facts(
+            "listed_fact",
+            [(1_u64, atom!("one")), (2_u64, atom!("two"))],
+        )?;
+        let predicate = Predicate::new(functor!("listed_fact/2"), Module::new("user"));
+        let clauses = context.list_predicate(&predicate)?;
+
+        assert_eq!(2, clauses.len());
+        assert_eq!(1, clauses[0].0.get_arg::<u64>(1)?);
+        assert_eq!(atom!("two"), clauses[1].0.get_arg::<Atom>(2)?);
+        assert_eq!(atom!("true"), clauses[1].1.get::<Atom>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_predicate_existence() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert!(!context.predicate_exists("exists_check", 1)?);
+        context.assert_facts("exists_check", [(42_u64,)])?;
+        assert!(context.predicate_exists("exists_check", 1)?);
+        assert!(context.predicate_exists("user:exists_check", 1)?);
+        assert!(!context.predicate_exists("exists_check", 2)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn assert_unique_skips_variants() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let fact = term! {context: unique_fact(1, _)}?;
+        assert!(context.assert_unique(&fact)?);
+        assert!(!context.assert_unique(&term! {context: unique_fact(1, _)}?)?);
+        assert!(context.assert_unique(&term! {context: unique_fact(2, _)}?)?);
+
+        let rule = context.term_from_string("unique_fact(X, Y) :- Y is X + 1")?;
+        assert!(context.assert_unique(&rule)?);
+        let renamed = context.term_from_string("user:(unique_fact(A, B) :- B is A + 1)")?;
+        assert!(!context.assert_unique(&renamed)?);
+
+        let predicate = Predicate::new(functor!("unique_fact/2"), Module::new("user"));
+        assert_eq!(3, context.list_predicate(&predicate)?.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn assert_and_retract_clauses() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        context.assertz(Some("rust_db"), &term! {context: counter(1)}?)?;
+        context.asserta(Some("rust_db"), &term! {context: counter(0)}?)?;
+        assert!(!context.predicate_exists("counter", 1)?);
+        assert!(context.predicate_exists("rust_db:counter", 1)?);
+
+        let value = context.new_term_ref();
+        assert!(context.retract(Some("rust_db"), &term! {context: counter(#&value)}?)?);
+        assert_eq!(0, value.get::<u64>()?);
+
+        let qualified = context.term_from_string("rust_db:counter(X)")?;
+        assert!(context.retract(None, &qualified)?);
+        assert!(!context.retract(Some("rust_db"), &term! {context: counter(_)}?)?);
+
+        Ok(())
+    }
+}
@@ -31,6 +31,25 @@ impl<A: IntoAtom> From<A> for Key {
     }
 }
 
+// Keys are ordered like prolog's standard order of terms, where
+// integers come before atoms, and atoms are compared by their text.
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (Key::Int(i1), Key::Int(i2)) => i1.cmp(i2),
+            (Key::Int(_), Key::Atom(_)) => std::cmp::Ordering::Less,
+            (Key::Atom(_), Key::Int(_)) => std::cmp::Ordering::Greater,
+            (Key::Atom(a1), Key::Atom(a2)) => a1.name().cmp(&a2.name()),
+        }
+    }
+}
+
 const INT_SHIFT: u8 = (std::mem::size_of::<fli::atom_t>() * 8 - 7) as u8;
 
 fn int_to_atom_t(val: u64) -> fli::atom_t {
@@ -362,6 +381,33 @@ impl<'a> Term<'a> {
         self.assert_term_handling_possible();
         unsafe { fli::PL_is_dict(self.term_ptr()) != 0 }
     }
+
+    /// Get the keys of this dictionary, sorted in the standard order of terms.
+    ///
+    /// Unlike iterating with [dict_entries](Context::dict_entries),
+    /// this does not create any terms for the values.
+    ///
+    /// If this is not a dictionary, this method will fail.
+    pub fn get_dict_keys(&self) -> PrologResult<Vec<Key>> {
+        if !self.is_dict() {
+            return Err(PrologError::Failure);
+        }
+
+        let functor: Functor = self.get()?;
+        let count = (functor.arity() as usize - 1) / 2;
+        let mut keys = Vec::with_capacity(count);
+        for index in 0..count {
+            let arg = index * 2 + 3;
+            let key = match attempt_opt(self.get_arg::<Atom>(arg))? {
+                Some(atom) => Key::Atom(atom),
+                None => Key::Int(self.get_arg(arg)?),
+            };
+            keys.push(key);
+        }
+        keys.sort();
+
+        Ok(keys)
+    }
 }
 
 impl<'a, T: QueryableContextType> Context<'a, T> {
@@ -398,6 +444,35 @@ impl<'a, T: QueryableContextType> Context<'a, T> {
             index: 0,
         }
     }
+
+    /// Retrieve all entries of the dictionary referred to by this term, with values of type `G`.
+    ///
+    /// Unlike with [dict_entries](Context::dict_entries), the entries
+    /// are sorted by key, in the standard order of terms. This is
+    /// not the order in which the dictionary stores them internally,
+    /// as atom keys are kept in an order that depends on the atom
+    /// table. This is useful for dictionaries of which all values are
+    /// of the same type, such as a dictionary of counts.
+    ///
+    /// If the term is not a dictionary, or if any of the values can't
+    /// be retrieved as a `G`, this method will fail.
+    pub fn get_dict_entries<G: TermGetable>(&self, term: &Term) -> PrologResult<Vec<(Key, G)>> {
+        if !term.is_dict() {
+            return Err(PrologError::Failure);
+        }
+
+        let frame = self.open_frame();
+        let entries: PrologResult<Vec<_>> = frame
+            .dict_entries(term)
+            .map(|(key, value)| Ok((key, value.get::<G>()?)))
+            .collect();
+        frame.discard();
+
+        let mut entries = entries?;
+        entries.sort_by(|(key1, _), (key2, _)| key1.cmp(key2));
+
+        Ok(entries)
+    }
 }
 
 /// An iterator over the entries of a dict term.
@@ -590,6 +665,74 @@ mod tests {
         assert_eq!(Atom::new("quux"), val4.get().unwrap());
     }
 
+    #[test]
+    fn get_sorted_dict_entries() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let builder = DictBuilder::new()
+            .entry("zebra", 3_u64)
+            .entry("aardvark", 1_u64)
+            .entry(5, 4_u64)
+            .entry("moose", 2_u64);
+
+        let term = context.new_term_ref();
+        term.unify(&builder).unwrap();
+
+        let entries = context.get_dict_entries::<u64>(&term).unwrap();
+        assert_eq!(
+            vec![
+                (Key::Int(5), 4),
+                (Key::Atom(Atom::new("aardvark")), 1),
+                (Key::Atom(Atom::new("moose")), 2),
+                (Key::Atom(Atom::new("zebra")), 3),
+            ],
+            entries
+        );
+
+        let builder = DictBuilder::new()
+            .entry("a", 1_u64)
+            .entry("b", atomable("two"));
+        let term = context.new_term_ref();
+        term.unify(&builder).unwrap();
+        assert!(context
+            .get_dict_entries::<u64>(&term)
+            .unwrap_err()
+            .is_failure());
+    }
+
+    #[test]
+    fn get_dict_keys() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let builder = DictBuilder::new()
+            .entry("zebra", 3_u64)
+            .entry(5, 4_u64)
+            .entry("aardvark", atomable("one"));
+
+        let term = context.new_term_ref();
+        term.unify(&builder).unwrap();
+        assert_eq!(
+            vec![
+                Key::Int(5),
+                Key::Atom(Atom::new("aardvark")),
+                Key::Atom(Atom::new("zebra")),
+            ],
+            term.get_dict_keys().unwrap()
+        );
+
+        let empty = context.new_term_ref();
+        empty.unify(DictBuilder::new()).unwrap();
+        assert!(empty.get_dict_keys().unwrap().is_empty());
+
+        let nondict = context.new_term_ref();
+        nondict.unify(42_u64).unwrap();
+        assert!(nondict.get_dict_keys().unwrap_err().is_failure());
+    }
+
     #[test]
     fn iterate_nondict_as_dict() {
         let engine = Engine::new();
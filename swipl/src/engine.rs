@@ -9,8 +9,11 @@
 //! When using swipl-rs to implement foreign predicates as part of a
 //! loadable module, you generally do not have to worry about prolog
 //! engines, unless you're spawning extra threads.
+use std::fmt;
 use std::sync::atomic;
+use std::sync::{Condvar, Mutex};
 
+use crate::context::*;
 use crate::fli::*;
 use crate::init::*;
 
@@ -23,6 +26,19 @@ use crate::init::*;
 pub struct Engine {
     engine_ptr: PL_engine_t,
     active: atomic::AtomicBool,
+    on_destroy: DestroyCallbacks,
+}
+
+type DestroyCallback = Box<dyn FnOnce() + Send>;
+
+#[derive(Default)]
+struct DestroyCallbacks(Mutex<Vec<DestroyCallback>>);
+
+impl fmt::Debug for DestroyCallbacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let count = self.0.lock().map(|callbacks| callbacks.len()).unwrap_or(0);
+        write!(f, "DestroyCallbacks({})", count)
+    }
 }
 
 unsafe impl Send for Engine {}
@@ -87,6 +103,7 @@ impl Engine {
         Engine {
             engine_ptr,
             active: atomic::AtomicBool::new(false),
+            on_destroy: DestroyCallbacks::default(),
         }
     }
 
@@ -106,6 +123,7 @@ impl Engine {
         Engine {
             engine_ptr: current_engine_ptr(),
             active: atomic::AtomicBool::new(false),
+            on_destroy: DestroyCallbacks::default(),
         }
     }
 
@@ -181,6 +199,135 @@ impl Engine {
             _ => panic!("unknown result from PL_set_engine"),
         }
     }
+
+    /// Temporarily activate this engine, run the given function with a context for it, and restore whatever engine was active before.
+    ///
+    /// Unlike [activate](Engine::activate), this may be called while
+    /// another engine is active on this thread. That engine is
+    /// switched out for the duration of the call, and switched back
+    /// in afterwards, even if the function panics.
+    ///
+    /// This will panic if this engine is already active, either on
+    /// this thread or on another one.
+    ///
+    /// # Safety
+    /// While the function runs, the engine that was active before is
+    /// not, but any contexts and terms that belong to it are still
+    /// borrowable. These must not be used from within the function,
+    /// either directly or by capturing them in the closure. Only the
+    /// context passed to the function may be used.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// let engine1 = Engine::new();
+    /// let engine2 = Engine::new();
+    /// let _activation = engine1.activate();
+    ///
+    /// let answer = unsafe {
+    ///     engine2.with_active(|context| {
+    ///         let term = context.new_term_ref();
+    ///         term.unify(42_u64).unwrap();
+    ///         term.get::<u64>().unwrap()
+    ///     })
+    /// };
+    ///
+    /// assert_eq!(42, answer);
+    /// assert!(engine1.is_active());
+    /// ```
+    pub unsafe fn with_active<R, F: FnOnce(&Context<ActivatedEngine>) -> R>(&self, f: F) -> R {
+        let previous = if Self::some_engine_active() {
+            // an engine is active, so swipl is initialized
+            current_engine_ptr()
+        } else {
+            std::ptr::null_mut()
+        };
+        if previous == self.engine_ptr {
+            panic!("engine already activated");
+        }
+
+        // declared before the activation, so that it is dropped after.
+        let _restore = RestoreEngine(previous);
+        // it is always fine to detach the current engine.
+        PL_set_engine(std::ptr::null_mut(), std::ptr::null_mut());
+        let activation = self.activate();
+        let context: Context<_> = activation.into();
+
+        f(&context)
+    }
+
+    /// Return the number of atoms currently known to SWI-Prolog.
+    ///
+    /// This uses `statistics(atoms, N)`. Atoms are shared between
+    /// all engines, so this is a process-wide count. It is mostly
+    /// useful for detecting atom leaks, for example by asserting in
+    /// a test that the atom count does not keep growing across
+    /// repeated runs of some workload. Keep in mind that atoms are
+    /// only reclaimed on atom garbage collection, which can be
+    /// forced by calling `garbage_collect_atoms/0`.
+    ///
+    /// If this engine is not active, it is activated for the
+    /// duration of this call. This will panic if another engine is
+    /// active on this thread.
+    ///
+    /// This is meant for tests, and is only available in builds with
+    /// debug assertions enabled.
+    #[cfg(debug_assertions)]
+    pub fn atom_count(&self) -> u64 {
+        if self.is_active() {
+            // unsafe justification: this engine is active, so we
+            // know we're in some valid context for calling prolog.
+            let context = unsafe { unmanaged_engine_context() };
+            context_atom_count(&context)
+        } else {
+            let activation = self.activate();
+            let context: Context<_> = activation.into();
+            context_atom_count(&context)
+        }
+    }
+
+    /// Register a callback to be run when this engine is destroyed.
+    ///
+    /// Callbacks are run on drop of the engine, just before the
+    /// underlying prolog engine is destroyed, in reverse order of
+    /// registration. This is the place to clean up rust-side
+    /// resources that are tied to this engine, such as a connection
+    /// that was stashed away for use by foreign predicates.
+    ///
+    /// The engine is inactive when the callbacks run, and as they
+    /// run from a drop, they should not panic.
+    pub fn on_destroy<F: FnOnce() + Send + 'static>(&self, f: F) {
+        self.on_destroy.0.lock().unwrap().push(Box::new(f));
+    }
+}
+
+struct RestoreEngine(PL_engine_t);
+
+impl Drop for RestoreEngine {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            // unsafe justification: this engine was active on this
+            // thread before, and nothing else could have claimed it
+            // in the meantime, as its activation is still around.
+            unsafe {
+                PL_set_engine(self.0, std::ptr::null_mut());
+            }
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+fn context_atom_count<C: QueryableContextType>(context: &Context<C>) -> u64 {
+    let frame = context.open_frame();
+    let [key, count] = frame.new_term_refs();
+    key.unify(crate::atom!("atoms")).unwrap();
+    frame
+        .call_once(crate::pred!(statistics / 2), [&key, &count])
+        .expect("statistics(atoms, N) should succeed");
+    let result = count.get().expect("atom count should be an integer");
+    frame.discard();
+
+    result
 }
 
 /// Checks if the given engine pointer is the engine that is currently active on this thread.
@@ -213,6 +360,13 @@ impl<'a> Drop for EngineActivation<'a> {
 impl Drop for Engine {
     fn drop(&mut self) {
         assert!(!self.active.load(atomic::Ordering::Relaxed));
+        let callbacks = match self.on_destroy.0.get_mut() {
+            Ok(callbacks) => std::mem::take(callbacks),
+            Err(poisoned) => std::mem::take(poisoned.into_inner()),
+        };
+        for callback in callbacks.into_iter().rev() {
+            callback();
+        }
         // unsafe justification: we got this ptr with PL_create_engine so this should be good
         unsafe {
             PL_destroy_engine(self.engine_ptr);
@@ -220,6 +374,135 @@ impl Drop for Engine {
     }
 }
 
+/// A fixed-size pool of prolog engines, for sharing between threads.
+///
+/// Rather than creating a new engine for every unit of work, like a
+/// request in a web server, worker threads can acquire an engine from
+/// the pool, activate it, and hand it back when done. Engines are
+/// handed out as [PooledEngine] guards, which return their engine to
+/// the pool when dropped.
+///
+/// Any prolog state that is local to an engine, such as global
+/// variables, stays with the engine when it is returned. Code using
+/// the pool should not rely on getting the same engine twice, and
+/// should clean up such state where it matters.
+///
+/// Example:
+/// ```
+/// # use swipl::prelude::*;
+/// use std::sync::Arc;
+///
+/// let pool = Arc::new(EnginePool::new(2));
+/// let workers: Vec<_> = (0..4_u64)
+///     .map(|i| {
+///         let pool = pool.clone();
+///         std::thread::spawn(move || {
+///             let engine = pool.acquire();
+///             let activation = engine.activate();
+///             let context: Context<_> = activation.into();
+///             let term = context.new_term_ref();
+///             term.unify(i).unwrap();
+///             term.get::<u64>().unwrap()
+///         })
+///     })
+///     .collect();
+///
+/// let total: u64 = workers.into_iter().map(|w| w.join().unwrap()).sum();
+/// assert_eq!(6, total);
+/// ```
+#[derive(Debug)]
+pub struct EnginePool {
+    engines: Mutex<Vec<Engine>>,
+    available: Condvar,
+    size: usize,
+}
+
+impl EnginePool {
+    /// Create a pool with the given number of engines.
+    ///
+    /// All engines are created up front. If SWI-Prolog has not been
+    /// initialized yet, it'll be done here.
+    pub fn new(size: usize) -> Self {
+        let engines = (0..size).map(|_| Engine::new()).collect();
+
+        Self {
+            engines: Mutex::new(engines),
+            available: Condvar::new(),
+            size,
+        }
+    }
+
+    /// Return the total number of engines managed by this pool.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Return the number of engines that are currently not handed out.
+    pub fn available(&self) -> usize {
+        self.engines.lock().unwrap().len()
+    }
+
+    /// Acquire an engine from the pool, blocking until one is available.
+    ///
+    /// Note that this blocks forever for a pool of size 0, or if the
+    /// calling thread itself holds on to all engines.
+    pub fn acquire(&self) -> PooledEngine {
+        let mut engines = self.engines.lock().unwrap();
+        loop {
+            if let Some(engine) = engines.pop() {
+                return PooledEngine {
+                    pool: self,
+                    engine: Some(engine),
+                };
+            }
+            engines = self.available.wait(engines).unwrap();
+        }
+    }
+
+    /// Acquire an engine from the pool if one is available, without blocking.
+    pub fn try_acquire(&self) -> Option<PooledEngine> {
+        let engine = self.engines.lock().unwrap().pop()?;
+
+        Some(PooledEngine {
+            pool: self,
+            engine: Some(engine),
+        })
+    }
+
+    fn release(&self, engine: Engine) {
+        self.engines.lock().unwrap().push(engine);
+        self.available.notify_one();
+    }
+}
+
+/// An engine that was acquired from an [EnginePool].
+///
+/// This dereferences to the [Engine], which can then be activated as
+/// usual. On drop, the engine is returned to the pool. As an
+/// activation borrows its engine, it will always be dropped before
+/// this guard is.
+#[derive(Debug)]
+pub struct PooledEngine<'a> {
+    pool: &'a EnginePool,
+    engine: Option<Engine>,
+}
+
+impl<'a> std::ops::Deref for PooledEngine<'a> {
+    type Target = Engine;
+
+    fn deref(&self) -> &Engine {
+        self.engine.as_ref().unwrap()
+    }
+}
+
+impl<'a> Drop for PooledEngine<'a> {
+    fn drop(&mut self) {
+        if let Some(engine) = self.engine.take() {
+            self.pool.release(engine);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -237,6 +520,70 @@ mod tests {
         let _activation = engine.activate();
     }
 
+    #[cfg(debug_assertions)]
+    #[test]
+    fn count_atoms() {
+        let engine = Engine::new();
+        let inactive_count = engine.atom_count();
+        assert!(inactive_count > 0);
+
+        let activation = engine.activate();
+        assert!(engine.atom_count() > 0);
+        std::mem::drop(activation);
+        assert!(!engine.is_active());
+    }
+
+    #[test]
+    fn run_callbacks_on_destroy() {
+        use std::sync::Arc;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let engine = Engine::new();
+        for i in 0..3 {
+            let order = order.clone();
+            engine.on_destroy(move || order.lock().unwrap().push(i));
+        }
+
+        let activation = engine.activate();
+        std::mem::drop(activation);
+        assert!(order.lock().unwrap().is_empty());
+
+        std::mem::drop(engine);
+        assert_eq!(vec![2, 1, 0], *order.lock().unwrap());
+    }
+
+    #[test]
+    fn temporarily_switch_engines() {
+        let engine1 = Engine::new();
+        let engine2 = Engine::new();
+        let activation = engine1.activate();
+        let context: Context<_> = activation.into();
+        let term = context.new_term_ref();
+
+        // the closure only uses the context of engine2.
+        let answer = unsafe {
+            engine2.with_active(|context2| {
+                assert!(engine2.is_active());
+                assert!(!engine1.is_active());
+                let term2 = context2.new_term_ref();
+                term2.unify(42_u64).unwrap();
+                term2.get::<u64>().unwrap()
+            })
+        };
+        assert_eq!(42, answer);
+        assert!(engine1.is_active());
+        assert!(!engine2.is_active());
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            unsafe { engine2.with_active(|_| panic!("oops")) };
+        }));
+        assert!(result.is_err());
+        assert!(engine1.is_active());
+
+        term.unify(answer).unwrap();
+        assert_eq!(42, term.get::<u64>().unwrap());
+    }
+
     #[test]
     fn switch_between_engines() {
         let engine1 = Engine::new();
@@ -245,4 +592,46 @@ mod tests {
         std::mem::drop(activation1);
         let _activation2 = engine2.activate();
     }
+
+    #[test]
+    fn acquire_and_release_pooled_engines() {
+        let pool = EnginePool::new(2);
+        assert_eq!(2, pool.size());
+
+        let engine1 = pool.acquire();
+        let engine2 = pool.try_acquire().unwrap();
+        assert_eq!(0, pool.available());
+        assert!(pool.try_acquire().is_none());
+
+        let activation = engine1.activate();
+        assert!(engine1.is_active());
+        std::mem::drop(activation);
+
+        std::mem::drop(engine1);
+        assert_eq!(1, pool.available());
+        std::mem::drop(engine2);
+        assert_eq!(2, pool.available());
+    }
+
+    #[test]
+    fn share_engine_pool_between_threads() {
+        use std::sync::Arc;
+
+        let pool = Arc::new(EnginePool::new(1));
+        let held = pool.acquire();
+
+        let waiter = {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                let engine = pool.acquire();
+                let _activation = engine.activate();
+                engine.is_active()
+            })
+        };
+
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        std::mem::drop(held);
+        assert!(waiter.join().unwrap());
+        assert_eq!(1, pool.available());
+    }
 }
@@ -12,13 +12,27 @@ use super::engine::*;
 use super::fli::*;
 use super::term::*;
 
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+use lazy_static::*;
+use thiserror::Error;
 
 use crate::{term_getable, term_putable, unifiable};
 
+/// An error that occurred while creating a functor with [Functor::try_new].
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum FunctorError {
+    #[error("functor arity {0} is larger than the maximum of {}", MAX_ARITY)]
+    ArityTooLarge(usize),
+    #[error("SWI-Prolog failed to create the functor")]
+    CreationFailed,
+}
+
 /// A wrapper for a prolog functor.
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct Functor {
     functor: functor_t,
 }
@@ -38,15 +52,36 @@ impl Functor {
     ///
     /// This will panic if no prolog engine is active on this thread.
     pub fn new<A: IntoAtom>(name: A, arity: u16) -> Functor {
+        match Self::try_new(name, arity as usize) {
+            Ok(functor) => functor,
+            Err(FunctorError::ArityTooLarge(arity)) => panic!("functor arity is >1024: {}", arity),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Create a new functor from the given name and arity, returning an error if this is not possible.
+    ///
+    /// Unlike [new](Functor::new), this does not panic on an arity
+    /// that is too large. Instead, it returns
+    /// [FunctorError::ArityTooLarge]. This makes it safe to use with
+    /// arities that come from untrusted input.
+    ///
+    /// This will still panic if no prolog engine is active on this
+    /// thread.
+    pub fn try_new<A: IntoAtom>(name: A, arity: usize) -> Result<Functor, FunctorError> {
         assert_some_engine_is_active();
-        if arity as usize > MAX_ARITY {
-            panic!("functor arity is >1024: {}", arity);
+        if arity > MAX_ARITY {
+            return Err(FunctorError::ArityTooLarge(arity));
         }
         let atom = name.into_atom();
 
-        let functor = unsafe { PL_new_functor(atom.atom_ptr(), arity.try_into().unwrap()) };
+        // arities up to MAX_ARITY always fit in a size_t
+        let functor = unsafe { PL_new_functor(atom.atom_ptr(), arity as _) };
+        if functor == 0 {
+            return Err(FunctorError::CreationFailed);
+        }
 
-        unsafe { Functor::wrap(functor) }
+        Ok(unsafe { Functor::wrap(functor) })
     }
 
     /// Return the underlying `functor_t` which SWI-Prolog uses to refer to the functor.
@@ -182,6 +217,69 @@ impl LazyFunctor {
     }
 }
 
+/// A cache of functors, keyed by name and arity.
+///
+/// Where [LazyFunctor] caches a single functor whose name is known
+/// at compile time, this caches any amount of functors whose names
+/// are only known at runtime. This is useful when building many
+/// terms out of a small set of functors, as it avoids looking up the
+/// name atom and the functor each time.
+///
+/// As functors are never garbage collected, neither are the
+/// functors in this cache. The global cache used by
+/// [Functor::cached] is usually what you want.
+#[derive(Default)]
+pub struct FunctorCache {
+    functors: RwLock<HashMap<String, HashMap<u16, Functor>>>,
+}
+
+impl FunctorCache {
+    /// Create a new empty functor cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the functor with the given name and arity, creating it if it is not yet in the cache.
+    ///
+    /// This will panic if the functor has to be created and no
+    /// prolog engine is active on this thread.
+    pub fn get(&self, name: &str, arity: u16) -> Functor {
+        if let Some(functor) = self
+            .functors
+            .read()
+            .unwrap()
+            .get(name)
+            .and_then(|arities| arities.get(&arity))
+        {
+            return *functor;
+        }
+
+        *self
+            .functors
+            .write()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .entry(arity)
+            .or_insert_with(|| Functor::new(name, arity))
+    }
+}
+
+lazy_static! {
+    static ref GLOBAL_FUNCTOR_CACHE: FunctorCache = FunctorCache::new();
+}
+
+impl Functor {
+    /// Return the functor with the given name and arity from a global cache.
+    ///
+    /// The first call for a particular name and arity creates the
+    /// functor, and subsequent calls return it from the cache. See
+    /// [FunctorCache].
+    pub fn cached(name: &str, arity: u16) -> Functor {
+        GLOBAL_FUNCTOR_CACHE.get(name, arity)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -201,6 +299,22 @@ mod tests {
         assert_eq!(3, f.arity());
     }
 
+    #[test]
+    fn try_new_checks_arity() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        assert_eq!(0, Functor::try_new("nullary", 0).unwrap().arity());
+        assert_eq!(
+            MAX_ARITY,
+            Functor::try_new("widest", 1024).unwrap().arity() as usize
+        );
+        assert_eq!(
+            Err(FunctorError::ArityTooLarge(1025)),
+            Functor::try_new("too_wide", 1025)
+        );
+    }
+
     #[test]
     fn unify_same_functor_twice_succeeds() {
         let engine = Engine::new();
@@ -351,4 +465,22 @@ mod tests {
         assert_eq!(atom!("foo"), f.name());
         assert_eq!(3, f.arity());
     }
+
+    #[test]
+    fn cache_functors() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let cache = FunctorCache::new();
+        let f1 = cache.get("cached_functor", 2);
+        let f2 = cache.get("cached_functor", 2);
+        let f3 = cache.get("cached_functor", 3);
+        assert_eq!(f1, f2);
+        assert_ne!(f1, f3);
+        assert_eq!(Functor::new("cached_functor", 2), f1);
+        assert_eq!(f1, Functor::cached("cached_functor", 2));
+
+        let set: std::collections::HashSet<Functor> = vec![f1, f2, f3].into_iter().collect();
+        assert_eq!(2, set.len());
+    }
 }
@@ -0,0 +1,211 @@
+//! Global variables and prolog flags.
+//!
+//! SWI-Prolog global variables associate a value with an atom key,
+//! per thread. They come in two kinds. Non-backtrackable variables,
+//! set with `nb_setval/2`, keep a copy of their value until they are
+//! set again. Backtrackable variables, set with `b_setval/2`, have
+//! their assignment undone on backtracking. Both are a cheaper way
+//! to keep state across queries than asserting facts.
+//!
+//! Prolog flags are process-wide settings, like `double_quotes` or
+//! `occurs_check`, which are read with `current_prolog_flag/2` and
+//! changed with `set_prolog_flag/2`.
+use crate::atom::*;
+use crate::context::*;
+use crate::pred;
+use crate::result::*;
+use crate::term::*;
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Set a non-backtrackable global variable using `nb_setval/2`.
+    ///
+    /// The value is copied, so later changes to any term it was
+    /// unified from do not affect the stored value.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.set_global("request_count", 42_u64)?;
+    /// assert_eq!(Some(42), context.get_global::<_, u64>("request_count")?);
+    /// assert_eq!(None, context.get_global::<_, u64>("no_such_global")?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn set_global<A: IntoAtom, U: Unifiable>(&self, key: A, value: U) -> PrologResult<()> {
+        self.setval(pred!(nb_setval / 2), key.into_atom(), value)
+    }
+
+    /// Set a backtrackable global variable using `b_setval/2`.
+    ///
+    /// Unlike with [set_global](Context::set_global), the value is
+    /// not copied, and the assignment is undone when prolog
+    /// backtracks to before this call. Discarding a frame the
+    /// variable was set in therefore also undoes it.
+    pub fn set_global_backtrackable<A: IntoAtom, U: Unifiable>(
+        &self,
+        key: A,
+        value: U,
+    ) -> PrologResult<()> {
+        self.setval(pred!(b_setval / 2), key.into_atom(), value)
+    }
+
+    fn setval<U: Unifiable>(
+        &self,
+        setter: crate::callable::CallablePredicate<2>,
+        key: Atom,
+        value: U,
+    ) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [key_term, value_term] = frame.new_term_refs();
+        key_term.unify(key)?;
+        value_term.unify(value)?;
+        frame.call_once(setter, [&key_term, &value_term])?;
+        // closing keeps the assignment for backtrackable variables.
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Retrieve the value of a global variable using `nb_current/2`.
+    ///
+    /// This works for both kinds of global variables. If the variable
+    /// is not set, `None` is returned. If its value can't be
+    /// retrieved as a `G`, this fails.
+    pub fn get_global<A: IntoAtom, G: TermGetable>(&self, key: A) -> PrologResult<Option<G>> {
+        let frame = self.open_frame();
+        let [key_term, value_term] = frame.new_term_refs();
+        key_term.unify(key.into_atom())?;
+        let value = match frame.call_once(pred!(nb_current / 2), [&key_term, &value_term]) {
+            Ok(()) => value_term.get::<G>().map(Some),
+            Err(PrologError::Failure) => Ok(None),
+            Err(e) => Err(e),
+        };
+        frame.discard();
+
+        value
+    }
+
+    /// Remove a global variable using `nb_delete/1`.
+    ///
+    /// Removing a variable that was never set is not an error.
+    pub fn delete_global<A: IntoAtom>(&self, key: A) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let key_term = frame.new_term_ref();
+        key_term.unify(key.into_atom())?;
+        frame.call_once(pred!(nb_delete / 1), [&key_term])?;
+        frame.discard();
+
+        Ok(())
+    }
+
+    /// Set a prolog flag using `set_prolog_flag/2`.
+    ///
+    /// Setting a flag to a value it does not accept, or setting a
+    /// read-only flag, raises an exception.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.set_prolog_flag("occurs_check", atom!("true"))?;
+    /// assert_eq!(
+    ///     Some(atom!("true")),
+    ///     context.get_prolog_flag::<_, Atom>("occurs_check")?
+    /// );
+    /// #  context.set_prolog_flag("occurs_check", atom!("false"))?;
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn set_prolog_flag<A: IntoAtom, U: Unifiable>(
+        &self,
+        flag: A,
+        value: U,
+    ) -> PrologResult<()> {
+        self.setval(pred!(set_prolog_flag / 2), flag.into_atom(), value)
+    }
+
+    /// Retrieve the value of a prolog flag using `current_prolog_flag/2`.
+    ///
+    /// If there is no such flag, `None` is returned. If its value
+    /// can't be retrieved as a `G`, this fails.
+    pub fn get_prolog_flag<A: IntoAtom, G: TermGetable>(&self, flag: A) -> PrologResult<Option<G>> {
+        let frame = self.open_frame();
+        let [flag_term, value_term] = frame.new_term_refs();
+        flag_term.unify(flag.into_atom())?;
+        let value = match frame.call_once(pred!(current_prolog_flag / 2), [&flag_term, &value_term])
+        {
+            Ok(()) => value_term.get::<G>().map(Some),
+            Err(PrologError::Failure) => Ok(None),
+            Err(e) => Err(e),
+        };
+        frame.discard();
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn global_variables() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        context.set_global("rust_global", "hello")?;
+        assert_eq!(
+            Some("hello".to_string()),
+            context.get_global::<_, String>("rust_global")?
+        );
+        assert!(context
+            .get_global::<_, u64>("rust_global")
+            .unwrap_err()
+            .is_failure());
+
+        let frame = context.open_frame();
+        frame.set_global_backtrackable("rust_global", 42_u64)?;
+        assert_eq!(Some(42), frame.get_global::<_, u64>("rust_global")?);
+        frame.discard();
+        assert_eq!(
+            Some("hello".to_string()),
+            context.get_global::<_, String>("rust_global")?
+        );
+
+        context.delete_global("rust_global")?;
+        assert_eq!(None, context.get_global::<_, String>("rust_global")?);
+        context.delete_global("rust_global")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn prolog_flags() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert_eq!(
+            None,
+            context.get_prolog_flag::<_, Atom>("no_such_rust_flag")?
+        );
+        let bounded: Atom = context.get_prolog_flag("bounded")?.unwrap();
+        assert!(bounded == atom!("true") || bounded == atom!("false"));
+
+        assert!(context
+            .set_prolog_flag("bounded", atom!("maybe"))
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+}
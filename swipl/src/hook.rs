@@ -0,0 +1,557 @@
+//! Hooks for taking part in prolog's own processes from rust.
+//!
+//! SWI-Prolog allows a lot of its behavior to be customized through
+//! hook predicates. This module allows such hooks to be implemented
+//! using rust closures. It also allows whole predicates to be
+//! defined by a closure at runtime, for cases where the
+//! `predicates!` macro is too static.
+//!
+//! The closures behind hooks and closure predicates live for the
+//! remainder of the process, and apply to all engines. They are
+//! never dropped.
+use std::sync::{Arc, Mutex, RwLock};
+
+use lazy_static::*;
+
+use crate::prelude::*;
+
+type TermExpansionHook =
+    dyn Fn(&Context<Frame>, &Term, &Term) -> PrologResult<()> + Send + Sync + 'static;
+
+type HaltHook = dyn Fn(&Context<Frame>, i64) -> bool + Send + Sync + 'static;
+
+type MessageHandler = dyn Fn(MessageLevel, &str) + Send + Sync + 'static;
+
+type ClosurePredicate =
+    dyn Fn(&Context<Frame>, &[Term]) -> PrologResult<()> + Send + Sync + 'static;
+
+lazy_static! {
+    static ref TERM_EXPANSION_HOOKS: RwLock<Vec<Arc<TermExpansionHook>>> = RwLock::new(Vec::new());
+    static ref HALT_HOOKS: RwLock<Vec<Arc<HaltHook>>> = RwLock::new(Vec::new());
+    static ref HALT_HOOK_INSTALLED: Mutex<bool> = Mutex::new(false);
+    static ref MESSAGE_HANDLER: RwLock<Option<Arc<MessageHandler>>> = RwLock::new(None);
+    static ref MESSAGE_HOOK_INSTALLED: Mutex<bool> = Mutex::new(false);
+    static ref CLOSURE_PREDICATES: RwLock<Vec<Arc<ClosurePredicate>>> = RwLock::new(Vec::new());
+}
+
+/// The kind of a message printed through `print_message/2`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageLevel {
+    Error,
+    Warning,
+    Informational,
+    /// A message for a debug topic, as printed by `debug/3`.
+    Debug,
+    /// Any other kind, such as `help` or `banner`, by name.
+    Other(String),
+}
+
+impl MessageLevel {
+    fn from_kind(kind: &Term) -> PrologResult<Self> {
+        if kind.get::<Functor>() == Ok(Functor::new("debug", 1)) {
+            return Ok(MessageLevel::Debug);
+        }
+
+        let kind: Atom = kind.get()?;
+        Ok(match kind.name().as_str() {
+            "error" => MessageLevel::Error,
+            "warning" => MessageLevel::Warning,
+            "informational" => MessageLevel::Informational,
+            name => MessageLevel::Other(name.to_string()),
+        })
+    }
+}
+
+predicates! {
+    #[name("$rust_term_expansion")]
+    semidet fn rust_term_expansion(context, id, input, output) {
+        let id: u64 = id.get()?;
+        let hook = TERM_EXPANSION_HOOKS
+            .read()
+            .unwrap()
+            .get(id as usize)
+            .cloned();
+        let hook = match hook {
+            Some(hook) => hook,
+            None => return Err(PrologError::Failure),
+        };
+
+        let frame = context.open_frame();
+        let result = hook(&frame, input, output);
+        // closing rather than discarding keeps both the bindings of
+        // the output term and any exception that the hook raised.
+        frame.close();
+
+        result
+    }
+
+    #[name("$rust_halt_hook")]
+    semidet fn rust_halt_hook(context, status) {
+        let status: i64 = status.get()?;
+        let hooks = HALT_HOOKS.read().unwrap().clone();
+
+        let frame = context.open_frame();
+        let cancel = hooks.iter().any(|hook| hook(&frame, status));
+        frame.discard();
+
+        if !cancel {
+            return Err(PrologError::Failure);
+        }
+
+        let exception = term! {context: halt_requested(#status)}?;
+        context.raise_exception(&exception)
+    }
+
+    #[name("$rust_message_hook")]
+    semidet fn rust_message_hook(_context, kind, text) {
+        let handler = MESSAGE_HANDLER.read().unwrap().clone();
+        let handler = match handler {
+            Some(handler) => handler,
+            None => return Err(PrologError::Failure),
+        };
+
+        let level = MessageLevel::from_kind(kind)?;
+        let text: String = text.get()?;
+        handler(level, &text);
+
+        Ok(())
+    }
+
+    #[name("$rust_closure_predicate")]
+    semidet fn rust_closure_predicate(context, id, args) {
+        let id: u64 = id.get()?;
+        let closure = CLOSURE_PREDICATES
+            .read()
+            .unwrap()
+            .get(id as usize)
+            .cloned();
+        let closure = match closure {
+            Some(closure) => closure,
+            None => return Err(PrologError::Failure),
+        };
+
+        let args = context.term_list_vec(args);
+        let frame = context.open_frame();
+        let result = closure(&frame, &args);
+        // like with term expansion, closing keeps both the bindings
+        // the closure made to its arguments and any exception.
+        frame.close();
+
+        result
+    }
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Add a `term_expansion/2` hook that is implemented by the given closure.
+    ///
+    /// The closure is called with a context, the term that is to be
+    /// expanded, and an output term to unify with the expansion. If
+    /// the closure fails, the term is left for other hooks (or left
+    /// alone). Exceptions raised by the closure are propagated to
+    /// the code that is loading the term.
+    ///
+    /// Internally, this adds a clause to `user:term_expansion/2`
+    /// which calls into rust. Hooks therefore run in the order they
+    /// were added, after any clauses that already existed. As this
+    /// does not go through the module system, the closure is not
+    /// told which module is being loaded. Use
+    /// `prolog_load_context/2` to find out. The hook stays in place
+    /// for good, see the [module documentation](crate::hook).
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.add_term_expansion(|_context, input, output| {
+    ///     if input.get::<Atom>()? == atom!("rust_fact") {
+    ///         output.unify(Functor::new("rust_fact", 1))?;
+    ///         output.unify_arg(1, "from rust")
+    ///     } else {
+    ///         Err(PrologError::Failure)
+    ///     }
+    /// })?;
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn add_term_expansion<F>(&self, hook: F) -> PrologResult<()>
+    where
+        F: Fn(&Context<Frame>, &Term, &Term) -> PrologResult<()> + Send + Sync + 'static,
+    {
+        let id = {
+            let mut hooks = TERM_EXPANSION_HOOKS.write().unwrap();
+            if hooks.is_empty() && !register_rust_term_expansion_in_module(Some("$swipl_rs")) {
+                return self.raise_registration_error("'$swipl_rs':'$rust_term_expansion'/3");
+            }
+            hooks.push(Arc::new(hook));
+
+            hooks.len() - 1
+        };
+
+        let frame = self.open_frame();
+        let clause = frame.term_from_string(&format!(
+            "user:term_expansion(In, Out) :- '$swipl_rs':'$rust_term_expansion'({}, In, Out)",
+            id
+        ))?;
+        frame.call_once(pred!(assertz / 1), [&clause])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Add a hook that is called when prolog code calls `halt/1`, which may cancel the halt.
+    ///
+    /// Normally, prolog code calling `halt/0` or `halt/1` takes the
+    /// whole process down with it, which is rarely what an embedding
+    /// application wants. The given closure is called with a context
+    /// and the requested exit status when this happens. If it
+    /// returns `true`, the process keeps running, and the `halt/1`
+    /// call raises `halt_requested(Status)` instead. This exception
+    /// unwinds the running query like any other, so it can be caught
+    /// by `catch/3`, and is decoded by
+    /// [decode_exception](Context::decode_exception) as
+    /// [ExceptionKind::HaltRequested]. If the closure returns
+    /// `false`, the next hook is asked, and when no hook cancels,
+    /// halting proceeds as normal.
+    ///
+    /// This is implemented by wrapping `halt/1` with
+    /// `wrap_predicate/4`, which is done the first time a hook is
+    /// added. As `halt/0` calls `halt/1`, it is covered too. Halting
+    /// that does not go through `halt/1`, such as a call to
+    /// `PL_halt()` from C or the process exiting normally, is not
+    /// seen by the hooks.
+    ///
+    /// Like the other hooks, this one stays in place for good, see
+    /// the [module documentation](crate::hook).
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.add_halt_hook(|_context, status| status == 3)?;
+    ///
+    /// let result = context.call_once(pred!(halt / 1), [&term! {context: 3}?]);
+    /// assert!(result.unwrap_err().is_exception());
+    /// let exception = context.take_exception().unwrap();
+    /// assert_eq!(
+    ///     ExceptionKind::HaltRequested { status: 3 },
+    ///     context.decode_exception(&exception)?
+    /// );
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn add_halt_hook<F>(&self, hook: F) -> PrologResult<()>
+    where
+        F: Fn(&Context<Frame>, i64) -> bool + Send + Sync + 'static,
+    {
+        // like with the message hook, halt/1 is wrapped before the
+        // hook is stored, so that a failed wrap is retried on the
+        // next call.
+        let mut installed = HALT_HOOK_INSTALLED.lock().unwrap();
+        if !*installed {
+            if !register_rust_halt_hook_in_module(Some("$swipl_rs")) {
+                return self.raise_registration_error("'$swipl_rs':'$rust_halt_hook'/1");
+            }
+
+            let frame = self.open_frame();
+            let goal = frame.term_from_string(
+                "wrap_predicate(system:halt(Status), swipl_rs_halt, Wrapped, \
+                   ( integer(Status), '$swipl_rs':'$rust_halt_hook'(Status) \
+                   -> true \
+                   ; Wrapped \
+                   ))",
+            )?;
+            frame.call_once(pred!(call / 1), [&goal])?;
+            frame.close();
+            *installed = true;
+        }
+
+        HALT_HOOKS.write().unwrap().push(Arc::new(hook));
+
+        Ok(())
+    }
+
+    /// Route messages printed through `print_message/2` to the given handler, instead of to the error stream.
+    ///
+    /// The handler is called with the kind of the message and its
+    /// text, rendered the way prolog would have printed it, but
+    /// without the `Warning:` or `ERROR:` prefix. This makes it
+    /// possible to pass warnings and errors from prolog on to a
+    /// logging framework like `log` or `tracing`. Messages of kind
+    /// `silent` are never passed on.
+    ///
+    /// This is implemented with a clause for `user:message_hook/3`,
+    /// which is added the first time a handler is set. There is
+    /// only one handler for the whole process, so setting a handler
+    /// replaces the previous one. Since the handler is called for
+    /// messages from any engine, it has to be `Send` and `Sync`.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// context.set_message_handler(|level, text| match level {
+    ///     MessageLevel::Error => eprintln!("prolog error: {}", text),
+    ///     MessageLevel::Warning => eprintln!("prolog warning: {}", text),
+    ///     _ => println!("{}", text),
+    /// })?;
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn set_message_handler<F>(&self, handler: F) -> PrologResult<()>
+    where
+        F: Fn(MessageLevel, &str) + Send + Sync + 'static,
+    {
+        // the clause is installed before the handler is stored, so
+        // that a failed installation is retried on the next call.
+        let mut installed = MESSAGE_HOOK_INSTALLED.lock().unwrap();
+        if !*installed {
+            if !register_rust_message_hook_in_module(Some("$swipl_rs")) {
+                return self.raise_registration_error("'$swipl_rs':'$rust_message_hook'/2");
+            }
+
+            let frame = self.open_frame();
+            let clause = frame.term_from_string(
+                "user:message_hook(_, Kind, Lines) :- \
+                   Kind \\== silent, \
+                   with_output_to(string(Text), print_message_lines(current_output, '', Lines)), \
+                   '$swipl_rs':'$rust_message_hook'(Kind, Text)",
+            )?;
+            frame.call_once(pred!(assertz / 1), [&clause])?;
+            frame.close();
+            *installed = true;
+        }
+
+        MESSAGE_HANDLER.write().unwrap().replace(Arc::new(handler));
+
+        Ok(())
+    }
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Define a semidet predicate `name/arity` in the given module, implemented by the given closure.
+    ///
+    /// The closure is called with a context and the arguments of the
+    /// call. Returning `Ok(())` makes the predicate succeed, keeping
+    /// any bindings the closure made to its arguments. Returning
+    /// `Err(PrologError::Failure)` makes it fail, and exceptions
+    /// raised by the closure are propagated to the caller. If no
+    /// module is given, the predicate is defined in `user`.
+    ///
+    /// Unlike predicates defined with the `predicates!` macro, which
+    /// are registered with `PL_register_foreign()`, this works from
+    /// values that are only known at runtime, and the closure can
+    /// capture state. This is done by asserting a clause for the
+    /// predicate which calls into rust, so the predicate is dynamic,
+    /// and defining it again adds a second clause rather than
+    /// replacing the first. Predicates that need to be
+    /// nondeterministic, keeping state between retries, should be
+    /// written with a `nondet fn` in the `predicates!` macro instead.
+    ///
+    /// As with hooks, the closure is never dropped, see the [module
+    /// documentation](crate::hook).
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let offset = 10;
+    /// context.define_closure_predicate(None, "add_offset", 2, move |_context, args| {
+    ///     let x: u64 = args[0].get()?;
+    ///     args[1].unify(x + offset)
+    /// })?;
+    ///
+    /// let result = context.new_term_ref();
+    /// context.call_once(pred!(add_offset / 2), [&term! {context: 32}?, &result])?;
+    /// assert_eq!(42, result.get::<u64>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn define_closure_predicate<F>(
+        &self,
+        module: Option<&str>,
+        name: &str,
+        arity: u16,
+        closure: F,
+    ) -> PrologResult<()>
+    where
+        F: Fn(&Context<Frame>, &[Term]) -> PrologResult<()> + Send + Sync + 'static,
+    {
+        let id = {
+            let mut closures = CLOSURE_PREDICATES.write().unwrap();
+            if closures.is_empty() && !register_rust_closure_predicate_in_module(Some("$swipl_rs"))
+            {
+                return self.raise_registration_error("'$swipl_rs':'$rust_closure_predicate'/2");
+            }
+            closures.push(Arc::new(closure));
+
+            closures.len() - 1
+        };
+
+        let frame = self.open_frame();
+        let clause = frame.term_from_string(&format!(
+            "Module:Head :- Head =.. [_|Args], '$swipl_rs':'$rust_closure_predicate'({}, Args)",
+            id
+        ))?;
+        let head = frame.new_term_ref();
+        clause.unify_arg(1, &head)?;
+        head.unify_arg(1, Atom::new(module.unwrap_or("user")))?;
+        head.unify_arg(2, Functor::new(name, arity))?;
+        frame.call_once(pred!(assertz / 1), [&clause])?;
+        frame.close();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn term_expansion_from_closure() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        context.add_term_expansion(|context, input, output| {
+            if input.get::<Functor>()? != Functor::new("rust_expand_me", 1) {
+                return Err(PrologError::Failure);
+            }
+
+            let arg = context.new_term_ref();
+            input.unify_arg(1, &arg)?;
+            output.unify(Functor::new("expanded_by_rust", 1))?;
+            output.unify_arg(1, &arg)
+        })?;
+
+        let [input, output] = context.new_term_refs();
+        input.unify(term! {context: rust_expand_me(42)}?)?;
+        context.call_once(pred!(expand_term / 2), [&input, &output])?;
+        assert_eq!(
+            Functor::new("expanded_by_rust", 1),
+            output.get::<Functor>()?
+        );
+        assert_eq!(42, output.get_arg::<u64>(1)?);
+
+        let [input, output] = context.new_term_refs();
+        input.unify(term! {context: leave_me_alone(42)}?)?;
+        context.call_once(pred!(expand_term / 2), [&input, &output])?;
+        assert_eq!(input, output);
+
+        Ok(())
+    }
+
+    #[test]
+    fn message_handler_receives_messages() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let received: Arc<RwLock<Vec<(MessageLevel, String)>>> = Default::default();
+        let sink = received.clone();
+        context.set_message_handler(move |level, text| {
+            sink.write().unwrap().push((level, text.to_string()))
+        })?;
+
+        let message = term! {context: format("hello from ~w", [prolog])}?;
+        let warning = term! {context: warning}?;
+        context.call_once(pred!(print_message / 2), [&warning, &message])?;
+        let silent = term! {context: silent}?;
+        context.call_once(pred!(print_message / 2), [&silent, &message])?;
+
+        // the handler also sees messages printed by tests running in
+        // parallel, so only the ones printed here are looked at.
+        let received = received.read().unwrap();
+        let hello: Vec<_> = received
+            .iter()
+            .filter(|(_, text)| text.contains("hello from prolog"))
+            .collect();
+        assert_eq!(1, hello.len());
+        assert_eq!(MessageLevel::Warning, hello[0].0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn closure_predicates() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let calls = Arc::new(RwLock::new(0_u64));
+        let counter = calls.clone();
+        context.define_closure_predicate(
+            Some("closure_test"),
+            "even",
+            1,
+            move |_context, args| {
+                *counter.write().unwrap() += 1;
+                if args[0].get::<u64>()? % 2 == 0 {
+                    Ok(())
+                } else {
+                    Err(PrologError::Failure)
+                }
+            },
+        )?;
+
+        context.call_once(pred!("closure_test:even/1"), [&term! {context: 42}?])?;
+        assert!(context
+            .call_once(pred!("closure_test:even/1"), [&term! {context: 41}?])
+            .unwrap_err()
+            .is_failure());
+        assert_eq!(2, *calls.read().unwrap());
+
+        Ok(())
+    }
+
+    #[test]
+    fn halt_hook_cancels_halt() -> PrologResult<()> {
+        // if the hook does not work, the halt takes the whole process
+        // down, so the test runs itself again in a child process.
+        if std::env::var_os("SWIPL_RS_HALT_HOOK_CHILD").is_none() {
+            let output = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["hook::tests::halt_hook_cancels_halt", "--exact"])
+                .env("SWIPL_RS_HALT_HOOK_CHILD", "1")
+                .output()
+                .unwrap();
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(output.status.success(), "{}", stdout);
+            assert!(stdout.contains("1 passed"), "{}", stdout);
+
+            return Ok(());
+        }
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        context.add_halt_hook(|_, status| status == 42)?;
+
+        let result = context.call_once(pred!(halt / 1), [&term! {context: 42}?]);
+        assert!(result.unwrap_err().is_exception());
+        let exception = context.take_exception().unwrap();
+        assert_eq!(
+            ExceptionKind::HaltRequested { status: 42 },
+            context.decode_exception(&exception)?
+        );
+
+        let status = context.new_term_ref();
+        let goal = term! {context: catch(halt(42), halt_requested(#&status), true)}?;
+        context.call_once(pred!(call / 1), [&goal])?;
+        assert_eq!(42, status.get::<i64>()?);
+
+        Ok(())
+    }
+}
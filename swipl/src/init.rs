@@ -15,8 +15,10 @@ use lazy_static::*;
 use std::convert::TryInto;
 use std::ffi::CString;
 use std::os::raw::c_int;
+use std::path::PathBuf;
 use std::sync::RwLockWriteGuard;
 use std::sync::{Arc, RwLock};
+use thiserror::Error;
 
 lazy_static! {
     static ref INITIALIZATION_STATE: Arc<RwLock<Option<Engine>>> = Arc::new(RwLock::new(None));
@@ -43,6 +45,24 @@ pub fn assert_swipl_is_initialized() {
 static ARG0: &[u8] = b"rust-swipl\0"; // fake program name
 static ARG1: &[u8] = b"--quiet\0"; // suppress swipl banner printing
 
+/// Error type for failed SWI-Prolog initialization.
+#[derive(Error, Debug)]
+pub enum SwiplInitError {
+    /// SWI-Prolog was initialized by this library before, but has
+    /// since been halted or cleaned up. It can't be initialized
+    /// again in the same process.
+    #[error("SWI-Prolog cannot be initialized again after it was halted or cleaned up")]
+    AlreadyHalted,
+    /// The home directory given through the `SWI_HOME_DIR`
+    /// environment variable does not exist.
+    #[error("SWI-Prolog home directory {} does not exist", .0.display())]
+    HomeNotFound(PathBuf),
+    /// `PL_initialise()` failed for some other reason. SWI-Prolog
+    /// usually prints the reason to the error stream.
+    #[error("SWI-Prolog failed to initialize")]
+    InitializationFailed,
+}
+
 /// Initialize SWI-Prolog.
 ///
 /// This requires a borrow to a MainEngineActivator, whose lifetime will be used to
@@ -51,19 +71,37 @@ static ARG1: &[u8] = b"--quiet\0"; // suppress swipl banner printing
 /// do nothing, and None will be returned. Otherwise, An
 /// `EngineActivation` will be returned containing the main prolog
 /// engine.
+///
+/// This panics if SWI-Prolog fails to initialize. Use
+/// [try_initialize_swipl] to handle this failure instead.
 pub fn initialize_swipl() -> Option<EngineActivation<'static>> {
+    try_initialize_swipl().unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Initialize SWI-Prolog, returning an error if this fails.
+///
+/// This works like [initialize_swipl], except that a failure of
+/// `PL_initialise` is returned as an error instead of causing a
+/// panic. This allows an application to carry on without prolog,
+/// for example when the SWI-Prolog installation cannot be found.
+pub fn try_initialize_swipl() -> Result<Option<EngineActivation<'static>>, SwiplInitError> {
     if is_swipl_initialized() {
-        return None;
+        return Ok(None);
     }
 
     // lock the rest of this initialization function to prevent concurrent initializers. Ideally this should happen in swipl itself, but unfortunately, it doesn't.
     let initialized = INITIALIZATION_STATE.write().unwrap();
     // There's actually a slight chance that initialization happened just now by some other thread. So check again.
     if initialized.is_some() {
-        return None;
+        if is_swipl_initialized() {
+            return Ok(None);
+        }
+
+        // we initialized prolog before, but it is gone now.
+        return Err(SwiplInitError::AlreadyHalted);
     }
 
-    initialize_internal(initialized)
+    initialize_internal(initialized).map(Some)
 }
 
 /// Initialize SWI-Prolog with a given saved state.
@@ -100,12 +138,12 @@ pub fn initialize_swipl_with_state(state: &'static [u8]) -> Option<EngineActivat
         return None;
     }
 
-    initialize_internal(initialized)
+    Some(initialize_internal(initialized).unwrap_or_else(|e| panic!("{}", e)))
 }
 
 fn initialize_internal(
     mut initialized: RwLockWriteGuard<Option<Engine>>,
-) -> Option<EngineActivation<'static>> {
+) -> Result<EngineActivation<'static>, SwiplInitError> {
     // TOOD we just pick "rust-swipl" as a fake program name here. This seems to work fine. But what we should really do is pass along the actual argv[0].
     let mut args: [*mut std::os::raw::c_char; 3] = [
         ARG0.as_ptr() as *mut std::os::raw::c_char,
@@ -114,19 +152,40 @@ fn initialize_internal(
     ];
     // unsafe justification: this initializes the swipl library and is idempotent
     // That said, there is actually a chance that some non-rust code is concurrently initializing prolog, which may lead to errors. There is unfortunately nothing that can be done about this.
-    unsafe { PL_initialise(2, args.as_mut_ptr()) };
+    if unsafe { PL_initialise(2, args.as_mut_ptr()) } == 0 {
+        // the home directory can only be blamed when it was given
+        // explicitly, as otherwise we don't know where prolog looked.
+        if let Some(home) = std::env::var_os("SWI_HOME_DIR").map(PathBuf::from) {
+            if !home.is_dir() {
+                return Err(SwiplInitError::HomeNotFound(home));
+            }
+        }
+
+        return Err(SwiplInitError::InitializationFailed);
+    }
     *initialized = Some(unsafe { Engine::from_current() });
 
-    Some(unsafe { std::mem::transmute((*initialized).as_ref().unwrap().set_activated()) })
+    Ok(unsafe { std::mem::transmute((*initialized).as_ref().unwrap().set_activated()) })
 }
 
 /// Initialize SWI-Prolog and immediately deactivate the main thread engine.
 ///
-/// If SWI-Prolog was already initialized, this will do nothing.
+/// If SWI-Prolog was already initialized, this will do nothing. This
+/// panics if SWI-Prolog fails to initialize.
 pub fn initialize_swipl_noengine() {
-    let activation = initialize_swipl();
+    try_initialize_swipl_noengine().unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Initialize SWI-Prolog and immediately deactivate the main thread
+/// engine, returning an error if initialization fails.
+///
+/// If SWI-Prolog was already initialized, this will do nothing.
+pub fn try_initialize_swipl_noengine() -> Result<(), SwiplInitError> {
+    let activation = try_initialize_swipl()?;
     // dropping the activation will deactivate the engine
     std::mem::drop(activation);
+
+    Ok(())
 }
 
 /// Initialize SWI-Prolog with a saved state and immediately deactivate the main thread engine.
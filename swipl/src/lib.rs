@@ -12,25 +12,33 @@
 pub mod consts;
 pub mod fli;
 
+pub mod arithmetic;
+pub mod assoc;
 pub mod atom;
 pub mod blob;
 pub mod callable;
 pub mod context;
+pub mod database;
 pub mod dict;
 pub mod engine;
 pub mod functor;
+pub mod global;
+pub mod hook;
 pub mod init;
+pub mod load;
 pub mod module;
+pub mod options;
 pub mod predicate;
 pub mod record;
 pub mod result;
 pub mod stream;
 pub mod term;
 pub mod text;
+pub mod value;
 
 pub mod prelude;
 
 pub use swipl_macros::{
     arc_blob, atom, clone_blob, functor, pred, predicates, prolog, term, wrapped_arc_blob,
-    wrapped_clone_blob,
+    wrapped_clone_blob, TermGetable, Unifiable,
 };
@@ -0,0 +1,419 @@
+//! Loading prolog code from files.
+//!
+//! Besides loading source files, SWI-Prolog can load quick load
+//! files (`.qlf`). These contain precompiled code, which loads a lot
+//! faster than source for large programs. Source code that is held
+//! in memory can be loaded with [consult_string](Context::consult_string).
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lazy_static::*;
+
+use crate::atom::*;
+use crate::context::*;
+use crate::functor::*;
+use crate::pred;
+use crate::result::*;
+use crate::term::*;
+use crate::{atom, term};
+
+lazy_static! {
+    static ref CONSULT_HOOK_INSTALLED: Mutex<bool> = Mutex::new(false);
+}
+
+const CONSULT_ERRORS_KEY: &str = "$swipl_rs_consult_errors";
+
+/// An error that occurred while loading source code with [consult_string](Context::consult_string).
+///
+/// The location is taken from the context of the `syntax_error` or
+/// `load_error` exception. Not every error carries a full
+/// location, so each part of it is optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsultError {
+    /// The file that was being loaded. For source loaded from a
+    /// string, this is the name it was loaded under.
+    pub file: Option<String>,
+    /// The line the error occurred on, counting from 1.
+    pub line: Option<u64>,
+    /// The column the error occurred on, counting from 0.
+    pub column: Option<u64>,
+    /// The error message, as prolog would print it.
+    pub message: String,
+}
+
+impl fmt::Display for ConsultError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+            if let Some(column) = self.column {
+                write!(f, "{}:", column)?;
+            }
+        }
+        if self.file.is_some() || self.line.is_some() {
+            write!(f, " ")?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConsultError {}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Load a `.qlf` file that was created with [qcompile](Context::qcompile).
+    ///
+    /// This uses `load_files/2`. Loading a `.qlf` file is only
+    /// possible if it was created by a compatible version of
+    /// SWI-Prolog.
+    ///
+    /// If the path is not valid UTF-8, a representation error is
+    /// raised.
+    pub fn load_qlf(&self, path: &Path) -> PrologResult<()> {
+        let frame = self.open_frame();
+        let [file, options] = frame.new_term_refs();
+        file.unify(path_atom(&frame, path)?)?;
+        options.unify(Nil)?;
+        frame.call_once(pred!(load_files / 2), [&file, &options])?;
+        frame.close();
+
+        Ok(())
+    }
+
+    /// Compile the given source file into a `.qlf` file using `qcompile/1`.
+    ///
+    /// The resulting file is placed next to the source file, with
+    /// its extension replaced by `.qlf`. Its path is returned. Note
+    /// that `qcompile/1` also loads the source file.
+    ///
+    /// If the path is not valid UTF-8, a representation error is
+    /// raised.
+    ///
+    /// Example:
+    /// ```no_run
+    /// # use swipl::prelude::*;
+    /// # use std::path::Path;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let qlf = context.qcompile(Path::new("knowledge_base.pl"))?;
+    ///
+    /// // and later, on startup
+    /// context.load_qlf(&qlf)?;
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn qcompile(&self, source: &Path) -> PrologResult<PathBuf> {
+        let frame = self.open_frame();
+        let file = frame.new_term_ref();
+        file.unify(path_atom(&frame, source)?)?;
+        frame.call_once(pred!(qcompile / 1), [&file])?;
+        frame.close();
+
+        Ok(source.with_extension("qlf"))
+    }
+
+    /// Load prolog source code from a string, as if it was a file with the given name.
+    ///
+    /// This uses `load_files/2` with the `stream(Stream)` option. So
+    /// consulting the same name again replaces the clauses that were
+    /// loaded the previous time, just like reconsulting a file does.
+    ///
+    /// SWI-Prolog does not stop loading at a syntax error. Instead,
+    /// it prints the error and continues with the next clause. This
+    /// function captures the errors that would have been printed,
+    /// and returns the first one as a [ConsultError], with the
+    /// location it occurred at. The clauses without errors are
+    /// still loaded in that case. Exceptions that escape from
+    /// `load_files/2` are also turned into a `ConsultError` and
+    /// cleared.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let error = context
+    ///     .consult_string("rules", "good(1).\nbad(.\n")
+    ///     .unwrap_err();
+    /// assert_eq!(Some(2), error.line);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn consult_string(&self, name: &str, source: &str) -> Result<(), ConsultError> {
+        let result = self
+            .install_consult_hook()
+            .and_then(|_| self.consult_string_internal(name, source));
+
+        match result {
+            Ok(None) => Ok(()),
+            Ok(Some(error)) => Err(error),
+            Err(e) => {
+                let message = match e {
+                    PrologError::Failure => "loading failed".to_string(),
+                    PrologError::Exception => {
+                        let message = PrologException.to_string();
+                        self.clear_exception();
+
+                        message
+                    }
+                };
+
+                Err(ConsultError {
+                    file: Some(name.to_string()),
+                    line: None,
+                    column: None,
+                    message,
+                })
+            }
+        }
+    }
+
+    fn install_consult_hook(&self) -> PrologResult<()> {
+        let mut installed = CONSULT_HOOK_INSTALLED.lock().unwrap();
+        if *installed {
+            return Ok(());
+        }
+
+        // errors are collected in a global variable, which is only a
+        // list while a consult_string is in progress. Global
+        // variables are local to the thread, so concurrent loads
+        // don't see each other's errors.
+        let frame = self.open_frame();
+        let clause = frame.term_from_string(
+            "user:message_hook(Message, error, _) :- \
+               nb_current('$swipl_rs_consult_errors', Errors), \
+               is_list(Errors), \
+               ( source_location(File, Line) -> Location = File:Line ; Location = none ), \
+               nb_setval('$swipl_rs_consult_errors', [error(Message, Location)|Errors])",
+        )?;
+        frame.call_once(pred!(asserta / 1), [&clause])?;
+        frame.close();
+        *installed = true;
+
+        Ok(())
+    }
+
+    fn consult_string_internal(
+        &self,
+        name: &str,
+        source: &str,
+    ) -> PrologResult<Option<ConsultError>> {
+        let result = self
+            .set_global(CONSULT_ERRORS_KEY, Nil)
+            .and_then(|_| self.consult_string_collecting_errors(name, source));
+        // the errors are reset however loading went, as otherwise the
+        // message hook would swallow every error message printed on
+        // this thread from now on. This has to work with an exception
+        // pending, so that one is put aside for the duration.
+        let reset = unsafe {
+            with_cleared_exception(|| self.set_global(CONSULT_ERRORS_KEY, atom!("none")))
+        };
+
+        result.and_then(|result| reset.map(|_| result))
+    }
+
+    fn consult_string_collecting_errors(
+        &self,
+        name: &str,
+        source: &str,
+    ) -> PrologResult<Option<ConsultError>> {
+        let frame = self.open_frame();
+        let [key, id, text, stream, thrown] = frame.new_term_refs();
+        key.unify(Atom::new(CONSULT_ERRORS_KEY))?;
+        id.unify(Atom::new(name))?;
+        text.unify(source)?;
+        frame.call_once(pred!(open_string / 2), [&text, &stream])?;
+        let options = term! {frame: [stream(#&stream)]}?;
+        let loaded = frame.call_once(pred!(load_files / 2), [&id, &options]);
+        if let Err(PrologError::Exception) = loaded {
+            frame.with_exception(|e| thrown.unify(e.unwrap()))?;
+            frame.clear_exception();
+        }
+        frame.call_once(pred!(close / 1), [&stream])?;
+
+        let collected = frame.new_term_ref();
+        frame.call_once(pred!(nb_getval / 2), [&key, &collected])?;
+
+        // the hook adds errors to the front of the list, so the first
+        // error is the last one in it.
+        let result = match frame.term_list_vec(&collected).last() {
+            Some(error) => {
+                let [message, location] = frame.new_term_refs();
+                error.unify_arg(1, &message)?;
+                error.unify_arg(2, &location)?;
+                Some(consult_error(&frame, name, &message, &location)?)
+            }
+            None if !thrown.is_var() => {
+                let location = term! {frame: none}?;
+                Some(consult_error(&frame, name, &thrown, &location)?)
+            }
+            None if loaded.is_err() => Some(ConsultError {
+                file: Some(name.to_string()),
+                line: None,
+                column: None,
+                message: "loading failed".to_string(),
+            }),
+            None => None,
+        };
+        frame.discard();
+
+        Ok(result)
+    }
+}
+
+fn consult_error<T: QueryableContextType>(
+    context: &Context<T>,
+    name: &str,
+    message: &Term,
+    location: &Term,
+) -> PrologResult<ConsultError> {
+    let mut error = ConsultError {
+        file: Some(name.to_string()),
+        line: None,
+        column: None,
+        message: message_string(context, message)?,
+    };
+
+    if location.get::<Functor>() == Ok(Functor::new(":", 2)) {
+        if let Ok(file) = location.get_arg::<Atom>(1) {
+            error.file = Some(file.name());
+        }
+        error.line = location.get_arg::<u64>(2).ok();
+    }
+
+    // syntax errors and load errors carry a context saying exactly
+    // where they occurred, which is more precise than the location
+    // that was being loaded when the message got printed.
+    let [formal, error_context] = context.new_term_refs();
+    if message.get::<Functor>() == Ok(Functor::new("error", 2)) {
+        message.unify_arg(1, &formal)?;
+        message.unify_arg(2, &error_context)?;
+        if let Ok(functor) = error_context.get::<Functor>() {
+            if functor == Functor::new("file", 4) || functor == Functor::new("stream", 4) {
+                if functor.name() == atom!("file") {
+                    if let Ok(file) = error_context.get_arg::<Atom>(1) {
+                        error.file = Some(file.name());
+                    }
+                }
+                error.line = error_context.get_arg::<u64>(2).ok();
+                error.column = error_context.get_arg::<u64>(3).ok();
+            }
+        }
+    }
+
+    Ok(error)
+}
+
+fn message_string<T: QueryableContextType>(
+    context: &Context<T>,
+    message: &Term,
+) -> PrologResult<String> {
+    let frame = context.open_frame();
+    let [lines, rest, prefix, text] = frame.new_term_refs();
+    rest.unify(Nil)?;
+    frame.call_once(pred!(translate_message / 3), [message, &lines, &rest])?;
+    prefix.unify(atom!(""))?;
+    let goal = term! {frame: print_message_lines(current_output, #&prefix, #&lines)}?;
+    let output = term! {frame: string(#&text)}?;
+    frame.call_once(pred!(with_output_to / 2), [&output, &goal])?;
+    let result = text.get::<String>()?.trim_end().to_string();
+    frame.discard();
+
+    Ok(result)
+}
+
+fn path_atom<T: QueryableContextType>(context: &Context<T>, path: &Path) -> PrologResult<Atom> {
+    match path.to_str() {
+        Some(path) => Ok(Atom::new(path)),
+        None => context.raise_representation_error("encoding"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn qcompile_and_load_qlf() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let dir = std::env::temp_dir().join(format!("swipl-rs-qlf-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("qlf_test.pl");
+        std::fs::write(&source, "qlf_test_fact(42).\n").unwrap();
+
+        let qlf = context.qcompile(&source)?;
+        assert_eq!(dir.join("qlf_test.qlf"), qlf);
+        assert!(qlf.exists());
+
+        context.load_qlf(&qlf)?;
+
+        let x = context.new_term_ref();
+        context.call_once(pred!(qlf_test_fact / 1), [&x])?;
+        assert_eq!(42, x.get::<u64>()?);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn load_qlf_with_non_utf8_path() {
+        use std::os::unix::ffi::OsStrExt;
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let path = std::path::Path::new(std::ffi::OsStr::from_bytes(b"caf\xe9.qlf"));
+        assert!(context.load_qlf(path).unwrap_err().is_exception());
+        context.clear_exception();
+    }
+
+    #[test]
+    fn consult_string_reports_syntax_error_location() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let error = context
+            .consult_string(
+                "consult_string_test",
+                "consult_good(1).\nconsult_bad(.\nconsult_good(2).\n",
+            )
+            .unwrap_err();
+        assert_eq!(Some(2), error.line);
+        assert!(error.column.is_some());
+        assert!(!error.message.is_empty());
+        assert!(!context.has_exception());
+        // errors are no longer collected once the load is done
+        assert_eq!(
+            Some(atom!("none")),
+            context.get_global::<_, Atom>("$swipl_rs_consult_errors")?
+        );
+
+        // clauses without errors were still loaded
+        context.call_term_once(&term! {context: consult_good(1)}?)?;
+        context.call_term_once(&term! {context: consult_good(2)}?)?;
+
+        // consulting again under the same name replaces the clauses
+        assert_eq!(
+            Ok(()),
+            context.consult_string("consult_string_test", "consult_good(3).\n")
+        );
+        let x = context.new_term_ref();
+        context.call_once(pred!(consult_good / 1), [&x])?;
+        assert_eq!(3, x.get::<u64>()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+//! Option lists.
+//!
+//! Many SWI-Prolog predicates, such as `read_term/2`,
+//! `write_term/2` and `open/4`, take a list of options of the form
+//! `[quoted(true), max_depth(10)]`. This module provides a builder for
+//! such lists.
+use super::fli;
+use super::prelude::*;
+
+/// A builder for prolog option lists.
+///
+/// Each option is a name with a single value, which is turned into a
+/// `name(Value)` compound in the resulting list. Options appear in
+/// the list in the order in which they were first added.
+///
+/// Example:
+/// ```
+/// # use swipl::prelude::*;
+/// # fn main() -> PrologResult<()> {
+/// #  let engine = Engine::new();
+/// #  let activation = engine.activate();
+/// #  let context: Context<_> = activation.into();
+/// let options = OptionList::new()
+///     .option("quoted", atom!("true"))
+///     .option("max_depth", 10_u64);
+///
+/// let term = term! {context: foo("A b")}?;
+/// let text = context.new_term_ref();
+/// let output = term! {context: string(#&text)}?;
+/// let goal = term! {context: write_term(#&term, #&options)}?;
+/// context.call_once(pred!(with_output_to / 2), [&output, &goal])?;
+/// assert_eq!("foo(\"A b\")", text.get::<String>()?);
+/// #  Ok(())
+/// # }
+/// ```
+pub struct OptionList<'a> {
+    options: Vec<(Atom, Box<dyn Unifiable + 'a>)>,
+}
+
+impl<'a> Default for OptionList<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> OptionList<'a> {
+    /// Create a new, empty option list builder.
+    pub fn new() -> Self {
+        Self {
+            options: Vec::new(),
+        }
+    }
+
+    /// Add an option with the given name and value.
+    ///
+    /// If an option with this name already exists, its value is
+    /// replaced, but it keeps its position in the list.
+    pub fn add_option<A: IntoAtom, U: Unifiable + 'a>(&mut self, name: A, value: U) {
+        let name = name.into_atom();
+        let value = Box::new(value);
+        match self.options.iter_mut().find(|(n, _)| *n == name) {
+            Some(option) => option.1 = value,
+            None => self.options.push((name, value)),
+        }
+    }
+
+    /// Add an option with the given name and value.
+    ///
+    /// If an option with this name already exists, its value is
+    /// replaced, but it keeps its position in the list.
+    pub fn option<A: IntoAtom, U: Unifiable + 'a>(mut self, name: A, value: U) -> Self {
+        self.add_option(name, value);
+
+        self
+    }
+
+    /// Build the option list as a new term in the given context.
+    pub fn build<'b, T: QueryableContextType>(&self, context: &'b Context<T>) -> Term<'b> {
+        let term = context.new_term_ref();
+        term.put(self)
+            .expect("term put errored while building option list");
+
+        term
+    }
+}
+
+unsafe impl<'a> TermPutable for OptionList<'a> {
+    fn put(&self, term: &Term) {
+        term.assert_term_handling_possible();
+        let context = unsafe { unmanaged_engine_context() };
+
+        let [option, value_term] = context.new_term_refs();
+        unsafe {
+            fli::PL_put_nil(term.term_ptr());
+        }
+        for (name, value) in self.options.iter().rev() {
+            unsafe {
+                fli::PL_put_functor(option.term_ptr(), Functor::new(name, 1).functor_ptr());
+                fli::PL_get_arg(1, option.term_ptr(), value_term.term_ptr());
+            }
+            if !value.unify(&value_term) {
+                panic!("unifying option value failed while building option list");
+            }
+            unsafe {
+                fli::PL_cons_list(term.term_ptr(), option.term_ptr(), term.term_ptr());
+            }
+        }
+
+        unsafe {
+            option.reset();
+        }
+    }
+}
+
+unsafe impl<'a> Unifiable for OptionList<'a> {
+    fn unify(&self, term: &Term) -> bool {
+        term.assert_term_handling_possible();
+        let context = unsafe { unmanaged_engine_context() };
+
+        let list_term = context.new_term_ref();
+        self.put(&list_term);
+
+        let result = unsafe { fli::PL_unify(list_term.term_ptr(), term.term_ptr()) != 0 };
+        unsafe {
+            list_term.reset();
+        };
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_option_list() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let options = OptionList::new()
+            .option("quoted", atom!("true"))
+            .option("max_depth", 10_u64)
+            .option("variable_names", &x)
+            .option("quoted", atom!("false"));
+
+        let term = options.build(&context);
+        let text = context.string_from_term(&term)?;
+        assert_eq!(3, context.term_list_vec(&term).len());
+        assert!(text.starts_with("[quoted(false),max_depth(10),variable_names("));
+
+        x.unify(Nil)?;
+        let empty = OptionList::new().build(&context);
+        assert!(empty.unify(Nil).is_ok());
+        let expected =
+            context.term_from_string("[quoted(false),max_depth(10),variable_names([])]")?;
+        assert!(expected.unify(&options).is_ok());
+
+        Ok(())
+    }
+}
@@ -7,13 +7,21 @@
 //!
 //! This module provides functors and types for intearcting with
 //! prolog predicates.
+use std::collections::HashMap;
 use std::convert::TryInto;
+use std::ffi::CString;
+use std::sync::RwLock;
+
+use lazy_static::*;
 
 use super::atom::*;
+use super::context::*;
 use super::engine::*;
 use super::fli::*;
 use super::functor::*;
 use super::module::*;
+use super::result::*;
+use crate::{functor, pred};
 
 /// A wrapper for a prolog predicate.
 #[derive(Clone, Copy)]
@@ -48,6 +56,31 @@ impl Predicate {
         unsafe { Self::wrap(predicate) }
     }
 
+    /// Look up a predicate from an indicator like `"lists:member/2"`.
+    ///
+    /// The module part is optional, and defaults to `user`. This
+    /// returns `None` if the indicator is not of the form
+    /// `[Module:]Name/Arity`. As with [Predicate::new], the predicate
+    /// does not have to be defined for this to succeed.
+    ///
+    /// This will panic if no prolog engine is active on this thread.
+    pub fn from_indicator(indicator: &str) -> Option<Self> {
+        let (module, name_arity) = indicator.split_once(':').unwrap_or(("user", indicator));
+        let (name, arity) = name_arity.rsplit_once('/')?;
+        let arity: u16 = arity.parse().ok()?;
+        if name.is_empty() || module.is_empty() {
+            return None;
+        }
+
+        let name = CString::new(name).ok()?;
+        let module = CString::new(module).ok()?;
+        assert_some_engine_is_active();
+        let predicate =
+            unsafe { PL_predicate(name.as_ptr(), arity as std::os::raw::c_int, module.as_ptr()) };
+
+        Some(unsafe { Self::wrap(predicate) })
+    }
+
     /// Return the underlying `predicate_t` which SWI-Prolog uses to refer to the predicate.
     pub fn predicate_ptr(&self) -> predicate_t {
         self.predicate
@@ -133,3 +166,251 @@ impl Predicate {
         }
     }
 }
+
+/// A cache of predicates, keyed by functor and module name.
+///
+/// Looking up a predicate requires both its functor and the module
+/// it lives in. Two predicates with the same functor in different
+/// modules, such as `lists:append/3` and `user:append/3`, are
+/// entirely separate predicates, and are therefore cached
+/// separately.
+///
+/// Predicates are shared between all engines, so a single cache can
+/// be used from any engine. Just like functors, predicates are never
+/// garbage collected. The global cache used by [Predicate::cached]
+/// is usually what you want.
+#[derive(Default)]
+pub struct PredicateCache {
+    predicates: RwLock<HashMap<(Functor, String), Predicate>>,
+}
+
+impl PredicateCache {
+    /// Create a new empty predicate cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the predicate for the given functor in the given module, looking it up if it is not yet in the cache.
+    ///
+    /// This will panic if the predicate has to be looked up and no
+    /// prolog engine is active on this thread.
+    pub fn get(&self, functor: Functor, module: &str) -> Predicate {
+        if let Some(predicate) = self
+            .predicates
+            .read()
+            .unwrap()
+            .get(&(functor, module.to_string()))
+        {
+            return *predicate;
+        }
+
+        *self
+            .predicates
+            .write()
+            .unwrap()
+            .entry((functor, module.to_string()))
+            .or_insert_with(|| Predicate::new(functor, Module::new(module)))
+    }
+}
+
+lazy_static! {
+    static ref GLOBAL_PREDICATE_CACHE: PredicateCache = PredicateCache::new();
+}
+
+impl Predicate {
+    /// Return the predicate for the given functor in the given module from a global cache.
+    ///
+    /// See [PredicateCache].
+    pub fn cached(functor: Functor, module: &str) -> Predicate {
+        GLOBAL_PREDICATE_CACHE.get(functor, module)
+    }
+}
+
+/// Properties of a predicate, as reported by `predicate_property/2`.
+///
+/// See [Context::predicate_properties].
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct PredicateProperties {
+    /// The predicate has clauses or is a foreign predicate.
+    pub defined: bool,
+    /// The predicate can be modified using `assertz/1` and `retract/1`.
+    pub dynamic: bool,
+    /// The predicate is implemented in a foreign language, like rust.
+    pub foreign: bool,
+    /// The predicate is a system predicate.
+    pub built_in: bool,
+    /// The predicate is exported from its module.
+    pub exported: bool,
+    /// The number of clauses, for predicates that are not foreign.
+    pub number_of_clauses: Option<u64>,
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Retrieve the properties of the given predicate.
+    ///
+    /// Properties are looked up without autoloading the
+    /// predicate. For a predicate that is not defined, `defined` is
+    /// false, and so are most other properties.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let member = Predicate::from_indicator("lists:member/2").unwrap();
+    /// let properties = context.predicate_properties(&member)?;
+    /// assert!(properties.defined);
+    /// assert!(properties.exported);
+    /// assert!(!properties.dynamic);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn predicate_properties(&self, predicate: &Predicate) -> PrologResult<PredicateProperties> {
+        let frame = self.open_frame();
+        let [head, property, properties] = frame.new_term_refs();
+        let plain = frame.new_term_ref();
+        plain.unify(Functor::new(predicate.name(), predicate.arity()))?;
+        head.unify(functor!(":/2"))?;
+        head.unify_arg(1, predicate.module().name())?;
+        head.unify_arg(2, &plain)?;
+
+        let goal = frame.new_term_ref();
+        goal.unify(functor!("predicate_property/2"))?;
+        goal.unify_arg(1, &head)?;
+        goal.unify_arg(2, &property)?;
+        frame.call_once(pred!(findall / 3), [&property, &goal, &properties])?;
+
+        let mut result = PredicateProperties::default();
+        for property in frame.term_list_iter(&properties) {
+            if let Ok(atom) = property.get::<Atom>() {
+                match atom.name().as_str() {
+                    "defined" => result.defined = true,
+                    "dynamic" => result.dynamic = true,
+                    "foreign" => result.foreign = true,
+                    "built_in" => result.built_in = true,
+                    "exported" => result.exported = true,
+                    _ => {}
+                }
+            } else if property.get::<Functor>() == Ok(functor!("number_of_clauses/1")) {
+                result.number_of_clauses = Some(property.get_arg(1)?);
+            }
+        }
+        frame.discard();
+
+        Ok(result)
+    }
+
+    /// List the predicates that are defined in the given module.
+    ///
+    /// This is the equivalent of enumerating `current_predicate/1`
+    /// for the module. Predicates that are only imported into the
+    /// module are not included. The order of the predicates is
+    /// unspecified.
+    pub fn module_predicates(&self, module: &Module) -> PrologResult<Vec<Predicate>> {
+        let frame = self.open_frame();
+        let [indicator, name_arity, indicators] = frame.new_term_refs();
+        name_arity.unify(functor!("//2"))?;
+        indicator.unify(functor!(":/2"))?;
+        indicator.unify_arg(1, module.name())?;
+        indicator.unify_arg(2, &name_arity)?;
+
+        let goal = frame.new_term_ref();
+        goal.unify(functor!("current_predicate/1"))?;
+        goal.unify_arg(1, &indicator)?;
+        frame.call_once(pred!(findall / 3), [&name_arity, &goal, &indicators])?;
+
+        let mut result = Vec::new();
+        for name_arity in frame.term_list_iter(&indicators) {
+            let name: Atom = name_arity.get_arg(1)?;
+            let arity: u16 = name_arity.get_arg::<u64>(2)?.try_into().unwrap();
+            result.push(Predicate::new(Functor::new(name, arity), *module));
+        }
+        frame.discard();
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_predicates_per_module() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let cache = PredicateCache::new();
+        let append = Functor::new("append", 3);
+        let in_lists = cache.get(append, "lists");
+        let in_user = cache.get(append, "user");
+
+        assert_ne!(in_lists.predicate_ptr(), in_user.predicate_ptr());
+        assert_eq!(
+            in_lists.predicate_ptr(),
+            cache.get(append, "lists").predicate_ptr()
+        );
+        assert_eq!("lists", in_lists.module().name_string());
+        assert_eq!("user", in_user.module().name_string());
+        assert_eq!(
+            in_user.predicate_ptr(),
+            Predicate::cached(append, "user").predicate_ptr()
+        );
+    }
+
+    #[test]
+    fn predicates_from_indicators() {
+        let engine = Engine::new();
+        let _activation = engine.activate();
+
+        let member = Predicate::from_indicator("lists:member/2").unwrap();
+        assert_eq!("member", member.name_string());
+        assert_eq!(2, member.arity());
+        assert_eq!("lists", member.module().name_string());
+
+        let slash = Predicate::from_indicator("//2").unwrap();
+        assert_eq!("/", slash.name_string());
+        assert_eq!("user", slash.module().name_string());
+
+        assert!(Predicate::from_indicator("member").is_none());
+        assert!(Predicate::from_indicator("member/two").is_none());
+    }
+
+    #[test]
+    fn inspect_predicate_properties() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let module = Module::new("rust_props");
+        context.assertz(
+            Some("rust_props"),
+            &context.term_from_string("prop_fact(1)")?,
+        )?;
+        context.assertz(
+            Some("rust_props"),
+            &context.term_from_string("prop_fact(2)")?,
+        )?;
+
+        let fact = Predicate::from_indicator("rust_props:prop_fact/1").unwrap();
+        let properties = context.predicate_properties(&fact)?;
+        assert!(properties.defined);
+        assert!(properties.dynamic);
+        assert!(!properties.foreign);
+        assert_eq!(Some(2), properties.number_of_clauses);
+
+        let missing = Predicate::from_indicator("rust_props:no_such_pred/3").unwrap();
+        let properties = context.predicate_properties(&missing)?;
+        assert!(!properties.defined);
+        assert!(!properties.dynamic);
+
+        let predicates = context.module_predicates(&module)?;
+        assert_eq!(1, predicates.len());
+        assert_eq!("prop_fact", predicates[0].name_string());
+        assert_eq!(1, predicates[0].arity());
+
+        Ok(())
+    }
+}
@@ -1,20 +1,26 @@
 //! Module which re-exports all public symbols in this crate, for easy importing.
+pub use crate::arithmetic::*;
 pub use crate::atom::*;
 pub use crate::blob::*;
 pub use crate::callable::*;
 pub use crate::context::*;
+pub use crate::database::*;
 pub use crate::dict::*;
 pub use crate::engine::*;
 pub use crate::functor::*;
+pub use crate::hook::*;
 pub use crate::init::*;
+pub use crate::load::*;
 pub use crate::module::*;
+pub use crate::options::*;
 pub use crate::predicate::*;
 pub use crate::result::*;
 pub use crate::stream::*;
 pub use crate::term::*;
 pub use crate::text::*;
+pub use crate::value::*;
 
 pub use crate::{
     arc_blob, atom, clone_blob, functor, pred, predicates, prolog, term, term_getable,
-    term_putable, unifiable, wrapped_arc_blob, wrapped_clone_blob,
+    term_putable, unifiable, wrapped_arc_blob, wrapped_clone_blob, TermGetable, Unifiable,
 };
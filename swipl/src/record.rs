@@ -5,6 +5,9 @@
 //! erased. This module wraps such records, making the erase happen
 //! automatically on drop of a wrapper object.
 
+use std::os::raw::c_char;
+
+use super::context::*;
 use super::fli;
 use super::result::*;
 use super::term::*;
@@ -19,6 +22,13 @@ pub struct Record {
     record: fli::record_t,
 }
 
+// a `record_t` is a pointer which is normally not send or
+// sync. Records however live in the shared heap rather than on the
+// stacks of any one engine, so they can be recreated, duplicated and
+// erased from any thread.
+unsafe impl Send for Record {}
+unsafe impl Sync for Record {}
+
 impl Record {
     /// Extract a record from the given term.
     pub fn from_term(term: &Term) -> Record {
@@ -35,6 +45,17 @@ impl Record {
         term.assert_term_handling_possible();
         unsafe { into_prolog_result(fli::PL_recorded(self.record, term.term_ptr()) != 0) }
     }
+
+    /// Unify the given term with the recorded term.
+    ///
+    /// The recorded term is recreated into a temporary term
+    /// reference, which is reset right after unifying. This makes it
+    /// cheap to unify against the same constant term over and over
+    /// again, without it piling up on the stack. This is the same as
+    /// calling `target.unify(&record)`.
+    pub fn unify_into(&self, target: &Term) -> PrologResult<()> {
+        target.unify(self)
+    }
 }
 
 impl Clone for Record {
@@ -82,10 +103,97 @@ unifiable! {
     }
 }
 
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Serialize a term into a compact binary form using `PL_record_external()`.
+    ///
+    /// This is the same encoding as used by `fast_term_serialized/2`.
+    /// It is a lot faster and smaller than writing the term as text,
+    /// and it keeps shared subterms shared. Variables are preserved
+    /// as fresh variables. The bytes can be turned back into a term
+    /// with [fast_read](Context::fast_read), in any engine or
+    /// process.
+    ///
+    /// The format is tied to SWI-Prolog. It is only guaranteed to be
+    /// readable by the same version of SWI-Prolog on a machine with
+    /// the same word size, so it is suitable for caching or for
+    /// communication between processes, but not for long term
+    /// storage. Blobs other than text atoms, such as streams or rust
+    /// blobs, cannot be serialized this way.
+    pub fn fast_write(&self, term: &Term) -> PrologResult<Vec<u8>> {
+        self.assert_activated();
+        term.assert_term_handling_possible();
+
+        unsafe {
+            let mut size = 0;
+            let record = fli::PL_record_external(term.term_ptr(), &mut size);
+            if record.is_null() {
+                return match fli::pl_default_exception() != 0 {
+                    true => Err(PrologError::Exception),
+                    false => Err(PrologError::Failure),
+                };
+            }
+
+            let bytes = std::slice::from_raw_parts(record as *const u8, size).to_vec();
+            fli::PL_erase_external(record);
+
+            Ok(bytes)
+        }
+    }
+
+    /// Deserialize a term from bytes created with [fast_write](Context::fast_write), using `PL_recorded_external()`.
+    ///
+    /// The bytes start with a header that SWI-Prolog checks, failing
+    /// or raising an exception if they were written in a way that
+    /// this version can't read. Empty input fails.
+    ///
+    /// # Safety
+    /// `PL_recorded_external()` takes no length and does not validate
+    /// the data after the header. The bytes must be exactly as
+    /// returned by [fast_write](Context::fast_write). Truncated,
+    /// corrupted or maliciously crafted bytes lead to out of bounds
+    /// reads. Before reading bytes that came from disk or over the
+    /// network, make sure they are intact and come from a trusted
+    /// source, for example with a checksum or a signature.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let term = term! {context: point(1, 2, "label")}?;
+    /// let bytes = context.fast_write(&term)?;
+    ///
+    /// let copy = unsafe { context.fast_read(&bytes)? };
+    /// assert!(copy.structurally_equal(&term));
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub unsafe fn fast_read(&self, bytes: &[u8]) -> PrologResult<Term> {
+        self.assert_activated();
+        if bytes.is_empty() {
+            return Err(PrologError::Failure);
+        }
+
+        let term = self.new_term_ref();
+        let result = fli::PL_recorded_external(bytes.as_ptr() as *const c_char, term.term_ptr());
+        if result == 0 {
+            term.reset();
+
+            return match fli::pl_default_exception() != 0 {
+                true => Err(PrologError::Exception),
+                false => Err(PrologError::Failure),
+            };
+        }
+
+        Ok(term)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::context::*;
     use crate::engine::*;
     use crate::term;
 
@@ -140,6 +248,24 @@ mod tests {
         assert!(term1 == term2);
     }
 
+    #[test]
+    fn record_unify_into_repeatedly() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let record = term! {context: config(verbose, _)}.unwrap().record();
+
+        for level in 1..=3_u64 {
+            let setting = term! {context: config(_, #level)}.unwrap();
+            record.unify_into(&setting).unwrap();
+            assert_eq!(level, setting.get_arg::<u64>(2).unwrap());
+        }
+
+        let other = term! {context: config(quiet, 1)}.unwrap();
+        assert!(record.unify_into(&other).unwrap_err().is_failure());
+    }
+
     #[test]
     fn record_and_put_on_other_engine() {
         let record: Record;
@@ -165,6 +291,30 @@ mod tests {
         assert!(term1 == term2);
     }
 
+    #[test]
+    fn record_and_put_on_other_thread() {
+        let record = std::thread::spawn(|| {
+            let engine = Engine::new();
+            let activation = engine.activate();
+            let context: Context<_> = activation.into();
+
+            let term = term! {context: result(["a", "b"], 42)}.unwrap();
+            term.record()
+        })
+        .join()
+        .unwrap();
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term1 = term! {context: result(["a", "b"], 42)}.unwrap();
+        let term2 = context.new_term_ref();
+        term2.put(&record).unwrap();
+
+        assert!(term1 == term2);
+    }
+
     #[test]
     fn record_clone_drop_put() {
         let engine = Engine::new();
@@ -223,4 +373,42 @@ mod tests {
 
         assert!(!attempt(term2.unify(&record)).unwrap());
     }
+
+    #[test]
+    fn fast_write_and_read_roundtrip() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let x = context.new_term_ref();
+        let term = term! {context: foo(bar(baz, "text", 42), #&x, #&x, [1.5, _])}.unwrap();
+        let bytes = context.fast_write(&term).unwrap();
+        assert!(!bytes.is_empty());
+
+        let copy = unsafe { context.fast_read(&bytes) }.unwrap();
+        assert!(!copy.structurally_equal(&term));
+        assert!(copy.is_variant(&term));
+
+        assert!(unsafe { context.fast_read(&[]) }.unwrap_err().is_failure());
+    }
+
+    #[test]
+    fn fast_read_on_other_engine() {
+        let bytes = {
+            let engine = Engine::new();
+            let activation = engine.activate();
+            let context: Context<_> = activation.into();
+
+            let term = term! {context: foo(bar(baz, quux))}.unwrap();
+            context.fast_write(&term).unwrap()
+        };
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let expected = term! {context: foo(bar(baz, quux))}.unwrap();
+        let copy = unsafe { context.fast_read(&bytes) }.unwrap();
+        assert!(expected == copy);
+    }
 }
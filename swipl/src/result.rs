@@ -7,9 +7,17 @@
 //! early.
 //!
 //! This module also provides some transformations on prolog results.
+use std::ffi::CStr;
+use std::fmt;
 use thiserror::Error;
 
-use crate::context::{Context, QueryableContextType};
+use crate::atom::Atom;
+use crate::context::{Context, Frame, QueryableContextType};
+use crate::engine::Engine;
+use crate::fli;
+use crate::functor::Functor;
+use crate::term::Term;
+use swipl_macros::term;
 
 /// A prolog error.
 ///
@@ -36,10 +44,77 @@ impl PrologError {
     }
 }
 
+/// The reason a value could not be retrieved from a term.
+///
+/// This is returned by [Term::get_checked].
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum GetError {
+    /// The term does not hold a value of the requested type.
+    #[error("term is not a {type_name}")]
+    WrongType { type_name: &'static str },
+    /// The term holds a number of the right kind, but its value can't be represented in the requested type.
+    #[error("{type_name} is out of range for the requested type")]
+    OutOfRange { type_name: &'static str },
+    /// An exception was raised while retrieving the value.
+    #[error("prolog function threw an exception")]
+    Exception,
+}
+
 /// Unit type for errors which can only be an exception.
+///
+/// The exception term itself stays behind in the engine. When
+/// displayed, this renders the exception that is currently pending
+/// on the active engine, if any. This makes it possible to pass it
+/// on as a `Box<dyn std::error::Error>` while still getting a useful
+/// message out of it, as long as this happens before the exception
+/// is cleared and while the engine is still active.
 #[derive(Debug)]
 pub struct PrologException;
 
+impl fmt::Display for PrologException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match pending_exception_text() {
+            Some(text) => write!(f, "prolog threw an exception: {}", text),
+            None => write!(f, "prolog threw an exception"),
+        }
+    }
+}
+
+impl std::error::Error for PrologException {}
+
+fn pending_exception_text() -> Option<String> {
+    if !Engine::some_engine_active() {
+        return None;
+    }
+
+    unsafe {
+        let exception = fli::pl_default_exception();
+        if exception == 0 {
+            return None;
+        }
+
+        // writing the exception may run prolog code, which can't be
+        // done while the exception is pending, so it is temporarily
+        // cleared.
+        let backup = fli::PL_new_term_ref();
+        assert!(fli::PL_unify(backup, exception) != 0);
+        fli::PL_clear_exception();
+
+        let mut ptr = std::ptr::null_mut();
+        let flags = fli::CVT_WRITEQ | fli::BUF_DISCARDABLE | fli::REP_UTF8;
+        let text = if fli::PL_get_chars(backup, &mut ptr, flags) != 0 {
+            Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+        } else {
+            None
+        };
+
+        fli::PL_raise_exception(backup);
+        fli::PL_reset_term_refs(backup);
+
+        text
+    }
+}
+
 impl From<PrologException> for PrologError {
     fn from(_val: PrologException) -> PrologError {
         PrologError::Exception
@@ -178,3 +253,411 @@ pub fn unwrap_result<C: QueryableContextType, T>(c: &Context<C>, r: PrologResult
         Err(PrologStringError::Exception(s)) => panic!("{}", s),
     }
 }
+
+/// A decoded prolog exception.
+///
+/// The ISO standard prescribes that errors are thrown as terms of
+/// the form `error(Formal, Context)`, where the formal term says
+/// what went wrong. This enum covers the standard formal terms, with
+/// their arguments written out as text. This makes it possible to
+/// match on an exception after it has been cleared, without holding
+/// on to any term.
+///
+/// See [Context::decode_exception] for how to get one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExceptionKind {
+    /// `instantiation_error`: an argument was unbound where it shouldn't be.
+    InstantiationError,
+    /// `uninstantiation_error(Culprit)`: an argument was bound where it shouldn't be.
+    UninstantiationError { culprit: String },
+    /// `type_error(Type, Culprit)`: an argument was of the wrong type.
+    TypeError { expected: String, culprit: String },
+    /// `domain_error(Domain, Culprit)`: an argument had the right type, but an invalid value.
+    DomainError { domain: String, culprit: String },
+    /// `existence_error(Kind, Culprit)`: something, such as a procedure or a file, does not exist.
+    ExistenceError { kind: String, culprit: String },
+    /// `permission_error(Action, Kind, Culprit)`: an action is not allowed on something.
+    PermissionError {
+        action: String,
+        kind: String,
+        culprit: String,
+    },
+    /// `representation_error(What)`: an implementation limit was exceeded.
+    RepresentationError { what: String },
+    /// `evaluation_error(What)`: arithmetic went wrong, for example through a division by zero.
+    EvaluationError { what: String },
+    /// `resource_error(What)`: a resource, such as memory, ran out.
+    ResourceError { what: String },
+    /// `syntax_error(Message)`: text could not be parsed.
+    SyntaxError { message: String },
+    /// `halt_requested(Status)`: a call to `halt/1` was cancelled by
+    /// a hook added with [Context::add_halt_hook].
+    HaltRequested { status: i64 },
+    /// Any other `error(Formal, Context)` term.
+    Error { formal: String },
+    /// A thrown term that is not of the form `error(Formal, Context)`.
+    Other { term: String },
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Retrieve the pending exception and clear it.
+    ///
+    /// If there is an exception, a copy of the exception term is
+    /// returned, and the engine is no longer in an exceptional
+    /// state. If there is no exception, `None` is returned. The
+    /// returned term can be decoded with
+    /// [decode_exception](Context::decode_exception).
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let goal = context.term_from_string("atom_length(X, _)")?;
+    /// assert!(context.call_term_once(&goal).unwrap_err().is_exception());
+    ///
+    /// let exception = context.take_exception().unwrap();
+    /// assert!(!context.has_exception());
+    /// assert_eq!(
+    ///     ExceptionKind::InstantiationError,
+    ///     context.decode_exception(&exception)?
+    /// );
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn take_exception(&self) -> Option<Term> {
+        let term = self.new_term_ref();
+        let found = self.with_exception(|exception| match exception {
+            Some(exception) => {
+                term.unify(exception)
+                    .expect("unifying a fresh term ref with the exception should work");
+                true
+            }
+            None => false,
+        });
+
+        if found {
+            self.clear_exception();
+            Some(term)
+        } else {
+            unsafe { term.reset() };
+            None
+        }
+    }
+
+    /// Decode an exception term into an [ExceptionKind].
+    ///
+    /// Arguments of the formal term are written as text using
+    /// `term_string/2`.
+    pub fn decode_exception(&self, exception: &Term) -> PrologResult<ExceptionKind> {
+        let frame = self.open_frame();
+        let kind = decode_exception(&frame, exception);
+        frame.discard();
+
+        kind
+    }
+
+    /// Raise an `error(Formal, _)` exception, with the formal term built by the given function.
+    ///
+    /// This always returns `Err(PrologError::Exception)`, unless
+    /// building the formal term itself raised an exception.
+    pub fn raise_error<R, F>(&self, formal: F) -> PrologResult<R>
+    where
+        F: for<'b> FnOnce(&'b Context<'b, Frame>) -> PrologResult<Term<'b>>,
+    {
+        let frame = self.open_frame();
+        let result = formal(&frame).and_then(|formal| {
+            let error = term! {frame: error(#&formal, _)}?;
+            frame.raise_exception(&error)
+        });
+        // closing rather than discarding keeps the exception term intact.
+        frame.close();
+
+        result
+    }
+
+    /// Raise an `error(instantiation_error, _)` exception.
+    ///
+    /// This is the error to raise when an argument is unbound, but
+    /// should have been bound.
+    pub fn raise_instantiation_error<R>(&self) -> PrologResult<R> {
+        self.raise_error(|context| term! {context: instantiation_error})
+    }
+
+    /// Raise an `error(type_error(Expected, Culprit), _)` exception.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let culprit = term! {context: foo}?;
+    /// let result: PrologResult<()> = context.raise_type_error("integer", &culprit);
+    /// assert!(result.unwrap_err().is_exception());
+    ///
+    /// let exception = context.take_exception().unwrap();
+    /// assert_eq!(
+    ///     ExceptionKind::TypeError {
+    ///         expected: "integer".to_string(),
+    ///         culprit: "foo".to_string()
+    ///     },
+    ///     context.decode_exception(&exception)?
+    /// );
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn raise_type_error<R>(&self, expected: &str, culprit: &Term) -> PrologResult<R> {
+        let expected = Atom::new(expected);
+        self.raise_error(|context| term! {context: type_error(#&expected, #culprit)})
+    }
+
+    /// Raise an `error(domain_error(Domain, Culprit), _)` exception.
+    ///
+    /// This is the error to raise when an argument is of the right
+    /// type, but its value is not allowed, like a negative length.
+    pub fn raise_domain_error<R>(&self, domain: &str, culprit: &Term) -> PrologResult<R> {
+        let domain = Atom::new(domain);
+        self.raise_error(|context| term! {context: domain_error(#&domain, #culprit)})
+    }
+
+    /// Raise an `error(existence_error(Kind, Culprit), _)` exception.
+    ///
+    /// The kind describes what doesn't exist, such as `procedure`,
+    /// `source_sink` or `key`.
+    pub fn raise_existence_error<R>(&self, kind: &str, culprit: &Term) -> PrologResult<R> {
+        let kind = Atom::new(kind);
+        self.raise_error(|context| term! {context: existence_error(#&kind, #culprit)})
+    }
+
+    /// Raise an `error(permission_error(Action, Kind, Culprit), _)` exception.
+    ///
+    /// For example, modifying a static procedure raises
+    /// `permission_error(modify, static_procedure, foo/1)`.
+    pub fn raise_permission_error<R>(
+        &self,
+        action: &str,
+        kind: &str,
+        culprit: &Term,
+    ) -> PrologResult<R> {
+        let action = Atom::new(action);
+        let kind = Atom::new(kind);
+        self.raise_error(|context| term! {context: permission_error(#&action, #&kind, #culprit)})
+    }
+
+    /// Raise an `error(representation_error(What), _)` exception.
+    ///
+    /// This is the error to raise when a value can't be represented
+    /// on the rust side, such as an integer that does not fit in a
+    /// `u64`.
+    pub fn raise_representation_error<R>(&self, what: &str) -> PrologResult<R> {
+        let what = Atom::new(what);
+        self.raise_error(|context| term! {context: representation_error(#&what)})
+    }
+
+    /// Raise an `error(resource_error(What), _)` exception.
+    pub fn raise_resource_error<R>(&self, what: &str) -> PrologResult<R> {
+        let what = Atom::new(what);
+        self.raise_error(|context| term! {context: resource_error(#&what)})
+    }
+
+    /// Raise the error for a foreign predicate of this crate that could not be registered.
+    ///
+    /// The indicator is given as prolog text, like `'$swipl_rs':'$rust_foo'/1`.
+    pub(crate) fn raise_registration_error<R>(&self, indicator: &str) -> PrologResult<R> {
+        self.raise_error(|context| {
+            let indicator = context.term_from_string(indicator)?;
+            term! {context: permission_error(create, foreign_procedure, #&indicator)}
+        })
+    }
+}
+
+fn decode_exception<T: QueryableContextType>(
+    context: &Context<T>,
+    exception: &Term,
+) -> PrologResult<ExceptionKind> {
+    if exception.get::<Functor>() == Ok(Functor::new("halt_requested", 1)) {
+        if let Ok(status) = exception.get_arg::<i64>(1) {
+            return Ok(ExceptionKind::HaltRequested { status });
+        }
+    }
+
+    if exception.get::<Functor>() != Ok(Functor::new("error", 2)) {
+        return Ok(ExceptionKind::Other {
+            term: context.string_from_term(exception)?,
+        });
+    }
+
+    let formal = context.new_term_ref();
+    exception.unify_arg(1, &formal)?;
+    let arg = |index| -> PrologResult<String> {
+        let arg = context.new_term_ref();
+        formal.unify_arg(index, &arg)?;
+        context.string_from_term(&arg)
+    };
+
+    let (name, arity) = match formal.get::<Functor>() {
+        Ok(functor) => (functor.name_string(), functor.arity()),
+        Err(_) => (String::new(), 0),
+    };
+    Ok(match (name.as_str(), arity) {
+        ("instantiation_error", 0) => ExceptionKind::InstantiationError,
+        ("uninstantiation_error", 1) => ExceptionKind::UninstantiationError { culprit: arg(1)? },
+        ("type_error", 2) => ExceptionKind::TypeError {
+            expected: arg(1)?,
+            culprit: arg(2)?,
+        },
+        ("domain_error", 2) => ExceptionKind::DomainError {
+            domain: arg(1)?,
+            culprit: arg(2)?,
+        },
+        ("existence_error", 2) => ExceptionKind::ExistenceError {
+            kind: arg(1)?,
+            culprit: arg(2)?,
+        },
+        ("permission_error", 3) => ExceptionKind::PermissionError {
+            action: arg(1)?,
+            kind: arg(2)?,
+            culprit: arg(3)?,
+        },
+        ("representation_error", 1) => ExceptionKind::RepresentationError { what: arg(1)? },
+        ("evaluation_error", 1) => ExceptionKind::EvaluationError { what: arg(1)? },
+        ("resource_error", 1) => ExceptionKind::ResourceError { what: arg(1)? },
+        ("syntax_error", 1) => ExceptionKind::SyntaxError { message: arg(1)? },
+        _ => ExceptionKind::Error {
+            formal: context.string_from_term(&formal)?,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn display_pending_exception() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: foo}.unwrap();
+        let error = attempt_opt(term.get_ex::<u64>()).unwrap_err();
+        let message = error.to_string();
+        assert!(message.contains("type_error(integer,foo)"), "{}", message);
+        assert!(context.has_exception());
+
+        context.clear_exception();
+        let error: Box<dyn std::error::Error> = Box::new(error);
+        assert_eq!("prolog threw an exception", error.to_string());
+    }
+
+    #[test]
+    fn take_and_decode_exceptions() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        assert!(context.take_exception().is_none());
+
+        let cases = [
+            (
+                "atom_length(1, a)",
+                ExceptionKind::TypeError {
+                    expected: "integer".to_string(),
+                    culprit: "a".to_string(),
+                },
+            ),
+            (
+                "no_such_predicate_anywhere",
+                ExceptionKind::ExistenceError {
+                    kind: "procedure".to_string(),
+                    culprit: "no_such_predicate_anywhere/0".to_string(),
+                },
+            ),
+            (
+                "_ is 1 / 0",
+                ExceptionKind::EvaluationError {
+                    what: "zero_divisor".to_string(),
+                },
+            ),
+            (
+                "throw(oops)",
+                ExceptionKind::Other {
+                    term: "oops".to_string(),
+                },
+            ),
+            (
+                "throw(error(my_error(1), _))",
+                ExceptionKind::Error {
+                    formal: "my_error(1)".to_string(),
+                },
+            ),
+        ];
+        for (goal, expected) in cases {
+            let goal = context.term_from_string(goal)?;
+            assert!(context.call_term_once(&goal).unwrap_err().is_exception());
+            let exception = context.take_exception().unwrap();
+            assert!(!context.has_exception());
+            assert_eq!(expected, context.decode_exception(&exception)?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn raise_iso_errors() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        // only one exception can be pending, so each error is taken
+        // and decoded before raising the next one.
+        let check = |result: PrologResult<()>, expected: ExceptionKind| -> PrologResult<()> {
+            assert!(result.unwrap_err().is_exception());
+            let exception = context.take_exception().unwrap();
+            assert_eq!(expected, context.decode_exception(&exception)?);
+            Ok(())
+        };
+
+        let culprit = term! {context: foo(1)}?;
+        check(
+            context.raise_instantiation_error(),
+            ExceptionKind::InstantiationError,
+        )?;
+        check(
+            context.raise_domain_error("not_less_than_zero", &culprit),
+            ExceptionKind::DomainError {
+                domain: "not_less_than_zero".to_string(),
+                culprit: "foo(1)".to_string(),
+            },
+        )?;
+        check(
+            context.raise_existence_error("procedure", &culprit),
+            ExceptionKind::ExistenceError {
+                kind: "procedure".to_string(),
+                culprit: "foo(1)".to_string(),
+            },
+        )?;
+        check(
+            context.raise_permission_error("modify", "static_procedure", &culprit),
+            ExceptionKind::PermissionError {
+                action: "modify".to_string(),
+                kind: "static_procedure".to_string(),
+                culprit: "foo(1)".to_string(),
+            },
+        )?;
+        check(
+            context.raise_representation_error("max_arity"),
+            ExceptionKind::RepresentationError {
+                what: "max_arity".to_string(),
+            },
+        )?;
+        assert!(context.take_exception().is_none());
+
+        Ok(())
+    }
+}
@@ -37,11 +37,14 @@ use super::record::*;
 use super::result::*;
 use std::cmp::{Ordering, PartialOrd};
 use std::convert::TryInto;
+use std::ffi::CStr;
 use std::fmt;
 use std::fmt::Debug;
 use std::os::raw::c_char;
+use std::path::PathBuf;
+use std::time::Duration;
 
-use swipl_macros::term;
+use swipl_macros::{pred, term};
 
 #[cfg(feature = "serde")]
 pub mod de;
@@ -141,6 +144,12 @@ impl<'a> Term<'a> {
         unsafe { PL_is_integer(self.term) != 0 }
     }
 
+    /// Returns true if this term reference holds a float.
+    pub fn is_float(&self) -> bool {
+        self.assert_term_handling_possible();
+        unsafe { PL_is_float(self.term) != 0 }
+    }
+
     /// Reset terms created after this term, including this term itself.
     ///
     /// # Safety
@@ -219,6 +228,79 @@ impl<'a> Term<'a> {
         result2
     }
 
+    /// Check whether this term would unify with another term, without binding either of them.
+    ///
+    /// Unification is attempted inside a foreign frame, which is
+    /// discarded afterwards. This undoes any bindings that were
+    /// made, whether unification succeeded or not.
+    ///
+    /// If unification raises an error, which may happen when the
+    /// `occurs_check` flag is set to `error`, the terms are
+    /// considered not to unify and the error is cleared.
+    pub fn would_unify(&self, other: &Term) -> bool {
+        self.assert_term_handling_possible();
+        if self.origin.origin_engine_ptr() != other.origin.origin_engine_ptr() {
+            panic!("terms being unified are not part of the same engine");
+        }
+
+        unsafe {
+            let had_exception = pl_default_exception() != 0;
+            let frame = PL_open_foreign_frame();
+            let result = PL_unify(self.term, other.term) != 0;
+            PL_discard_foreign_frame(frame);
+            if !had_exception && pl_default_exception() != 0 {
+                PL_clear_exception();
+
+                return false;
+            }
+
+            result
+        }
+    }
+
+    /// Check whether this term is structurally equal to another term, as `==/2` does.
+    ///
+    /// Prolog has three relations between terms which are easily
+    /// confused:
+    /// - structural equality, checked by this method, is the
+    ///   strictest. Two terms are structurally equal if they are
+    ///   identical, and any variables in them are the very same
+    ///   variables. Two distinct unbound variables are therefore not
+    ///   structurally equal.
+    /// - being variants, checked by [is_variant](Term::is_variant),
+    ///   is like structural equality, except that variables may
+    ///   differ as long as they are consistently renamed. `f(X, Y)`
+    ///   is a variant of `f(A, B)`, but not of `f(A, A)`.
+    /// - unifiability, checked by [would_unify](Term::would_unify), is
+    ///   the loosest. `f(X, b)` and `f(a, Y)` unify, even though they
+    ///   are not variants.
+    ///
+    /// This is the same comparison that the [PartialEq]
+    /// implementation for terms does, which uses `PL_compare()`.
+    pub fn structurally_equal(&self, other: &Term) -> bool {
+        self == other
+    }
+
+    /// Check whether this term is a variant of another term, as `=@=/2` does.
+    ///
+    /// Two terms are variants of each other if they are equal up to
+    /// a consistent renaming of their variables. See
+    /// [structurally_equal](Term::structurally_equal) for how this
+    /// relates to the other ways of comparing terms.
+    pub fn is_variant(&self, other: &Term) -> bool {
+        self.assert_term_handling_possible();
+        if self.origin.origin_engine_ptr() != other.origin.origin_engine_ptr() {
+            panic!("terms being compared are not part of the same engine");
+        }
+
+        let context = unsafe { unmanaged_engine_context() };
+        let frame = context.open_frame();
+        let result = frame.call_once(pred!("=@=/2"), [self, other]).is_ok();
+        frame.discard();
+
+        result
+    }
+
     /// Retrieve data from the term reference.
     ///
     /// Any data type for which [TermGetable] has been implemented may
@@ -293,6 +375,51 @@ impl<'a> Term<'a> {
         }
     }
 
+    /// Retrieve data from the term reference, reporting why this was impossible.
+    ///
+    /// Where [get](Term::get) returns a plain failure, this
+    /// distinguishes between a term of the wrong type and a number
+    /// that does not fit the requested type. The latter is the case
+    /// when getting a negative integer as a `u64`, an integer that
+    /// is too large for an `i8`, or a float that can't be narrowed
+    /// to an `f32` without losing precision. Each type decides this
+    /// for itself through [TermGetable::matches_kind].
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let term = term! {context: 300}?;
+    /// assert_eq!(Ok(300), term.get_checked::<u16>());
+    /// assert_eq!(
+    ///     Err(GetError::OutOfRange { type_name: "integer" }),
+    ///     term.get_checked::<i8>()
+    /// );
+    /// assert_eq!(
+    ///     Err(GetError::WrongType { type_name: "string" }),
+    ///     term.get_checked::<String>()
+    /// );
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn get_checked<G: TermGetable>(&self) -> Result<G, GetError> {
+        match self.get::<G>() {
+            Ok(value) => Ok(value),
+            Err(PrologError::Exception) => Err(GetError::Exception),
+            Err(PrologError::Failure) => {
+                let type_name = G::name();
+                if G::matches_kind(self) {
+                    Err(GetError::OutOfRange { type_name })
+                } else {
+                    Err(GetError::WrongType { type_name })
+                }
+            }
+        }
+    }
+
     /// Retrieve data from the nth position of the given term. This
     /// assumes that the given term contains a functor.
     ///
@@ -303,11 +430,28 @@ impl<'a> Term<'a> {
     /// getting, an error was raised somewhere in the SWI-Prolog
     /// fli. If getting is not possible (because the term holds a
     /// variable, or an incompatible data type), an
-    /// `Err(PrologError::Failure)` will be returned. Otherwise the
-    /// result is an `Ok(data)`, with the requested data in it.
+    /// `Err(PrologError::Failure)` will be returned. This is also
+    /// the case when the term is not a compound, or when the index
+    /// is beyond its arity. Otherwise the result is an `Ok(data)`,
+    /// with the requested data in it.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let term = term! {context: person("alice", 42)}?;
+    /// assert_eq!("alice", term.get_arg::<String>(1)?);
+    /// assert_eq!(42, term.get_arg::<u64>(2)?);
+    /// assert!(term.get_arg::<u64>(3).unwrap_err().is_failure());
+    /// #  Ok(())
+    /// # }
+    /// ```
     pub fn get_arg<G: TermGetable>(&self, index: usize) -> PrologResult<G> {
         if index == 0 {
-            panic!("unify_arg was given index 0, but index starts at 1");
+            panic!("get_arg was given index 0, but index starts at 1");
         }
 
         self.assert_term_handling_possible();
@@ -316,6 +460,7 @@ impl<'a> Term<'a> {
 
         let result = unsafe { PL_get_arg(index.try_into().unwrap(), self.term, arg_ref) };
         if unsafe { pl_default_exception() != 0 } {
+            unsafe { PL_reset_term_refs(arg_ref) };
             return Err(PrologError::Exception);
         }
 
@@ -372,10 +517,27 @@ impl<'a> Term<'a> {
     /// Retrieve a &str from this term, and call the given function with it.
     ///
     /// This allows you to extract a string from a prolog string with
-    /// as few copies as possible.
+    /// as few copies as possible. Where possible, the `&str` borrows
+    /// the storage of the prolog string directly, so it is only valid
+    /// for the duration of the call. If the term is not a string,
+    /// the function is called with `None`.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let term = term! {context: "hello world"}?;
+    /// let words = term.get_str(|s| s.map(|s| s.split(' ').count()))?;
+    /// assert_eq!(Some(2), words);
+    /// #  Ok(())
+    /// # }
+    /// ```
     pub fn get_str<R, F>(&self, func: F) -> PrologResult<R>
     where
-        F: Fn(Option<&str>) -> R,
+        F: FnOnce(Option<&str>) -> R,
     {
         self.assert_term_handling_possible();
         let mut ptr = std::ptr::null_mut();
@@ -406,6 +568,41 @@ impl<'a> Term<'a> {
         Ok(func(arg))
     }
 
+    /// Retrieve a file path from this term.
+    ///
+    /// This uses `PL_get_file_name`, which accepts atoms and strings,
+    /// and converts the path to the conventions of the operating
+    /// system. Use [get_path_with_options](Term::get_path_with_options)
+    /// to also resolve path aliases like `library(lists)` or to get
+    /// an absolute path.
+    pub fn get_path(&self) -> PrologResult<PathBuf> {
+        self.get_path_with_options(PathOptions::default())
+    }
+
+    /// Retrieve a file path from this term, resolving it according to the given options.
+    ///
+    /// If the term cannot be turned into a path, or if the options
+    /// require the file to exist and it doesn't, an exception is
+    /// raised.
+    pub fn get_path_with_options(&self, options: PathOptions) -> PrologResult<PathBuf> {
+        self.assert_term_handling_possible();
+        let mut ptr = std::ptr::null_mut();
+        let result = unsafe {
+            PL_get_file_name(self.term, &mut ptr, options.flags() as std::os::raw::c_int)
+        };
+
+        if unsafe { pl_default_exception() != 0 } {
+            return Err(PrologError::Exception);
+        }
+        if result == 0 {
+            return Err(PrologError::Failure);
+        }
+
+        let name = unsafe { CStr::from_ptr(ptr) };
+
+        Ok(PathBuf::from(name.to_string_lossy().into_owned()))
+    }
+
     /// Retrieve an atom from this term, and call the given function with a borrow to it.
     ///
     /// We skip reference-counting for this atom which may be slightly
@@ -497,8 +694,146 @@ impl<'a> Term<'a> {
     pub fn record(&self) -> Record {
         Record::from_term(self)
     }
+
+    /// Unify this term with a list cell `[Head|Tail]`, returning fresh terms for the head and the tail.
+    ///
+    /// If this term is unbound, it gets bound to a new list cell
+    /// with unbound head and tail. If it already is a list cell,
+    /// its head and tail are returned. This is the building block
+    /// for producing an output list one element at a time: unify a
+    /// cell, unify the head with an element, then continue with the
+    /// tail, and finally unify the last tail with [Nil].
+    ///
+    /// This does the same thing as [Context::unify_list_functor],
+    /// with the head and tail belonging to the context of this term.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let list = context.new_term_ref();
+    /// let mut tail = list.clone();
+    /// for i in 1..=3_u64 {
+    ///     let (head, rest) = tail.unify_list_cell()?;
+    ///     head.unify(i)?;
+    ///     tail = rest;
+    /// }
+    /// tail.unify(Nil)?;
+    ///
+    /// assert_eq!(vec![1, 2, 3], list.get::<Vec<u64>>()?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn unify_list_cell(&self) -> PrologResult<(Term<'a>, Term<'a>)> {
+        self.assert_term_handling_possible();
+
+        unsafe {
+            let head = PL_new_term_ref();
+            let tail = PL_new_term_ref();
+            if PL_unify_list(self.term, head, tail) == 0 {
+                PL_reset_term_refs(head);
+
+                return match pl_default_exception() != 0 {
+                    true => Err(PrologError::Exception),
+                    false => Err(PrologError::Failure),
+                };
+            }
+
+            Ok((
+                Term::new(head, self.origin.clone()),
+                Term::new(tail, self.origin.clone()),
+            ))
+        }
+    }
+
+    /// Lazily iterate over the elements of the list in this term.
+    ///
+    /// Unlike [Context::term_list_iter], which stops silently at the
+    /// end of whatever cons cells there are, this iterator checks how
+    /// the list ends. If the final tail is anything other than `[]`,
+    /// such as an unbound variable in a partial list, the last item
+    /// yielded is an `Err(PartialListError)`. Iteration stops after
+    /// that.
+    ///
+    /// The list is walked one cell at a time, without collecting the
+    /// elements up front. Each element is a fresh term reference,
+    /// which belongs to the same context as this term. It is up to the
+    /// caller to clean those up, for example by iterating inside a
+    /// frame.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let list = term! {context: [1, 2, 3]}?;
+    /// let mut sum = 0;
+    /// for element in list.list_iter() {
+    ///     sum += element.unwrap().get::<u64>()?;
+    /// }
+    /// assert_eq!(6, sum);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn list_iter(&self) -> ListIter<'a> {
+        self.assert_term_handling_possible();
+        let cursor = unsafe { Term::new(PL_copy_term_ref(self.term), self.origin.clone()) };
+
+        ListIter {
+            cursor: Some(cursor),
+        }
+    }
+}
+
+/// A lazy iterator over the elements of a list.
+///
+/// See [Term::list_iter] for more information.
+pub struct ListIter<'a> {
+    cursor: Option<Term<'a>>,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = Result<Term<'a>, PartialListError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cursor = self.cursor.as_ref()?;
+        cursor.assert_term_handling_possible();
+
+        // the cursor is moved to the tail in place, so that walking
+        // the list only allocates the term references for the heads.
+        let head = unsafe { PL_new_term_ref() };
+        if unsafe { PL_get_list(cursor.term, head, cursor.term) } != 0 {
+            return Some(Ok(unsafe { Term::new(head, cursor.origin.clone()) }));
+        }
+
+        unsafe { PL_reset_term_refs(head) };
+        let is_nil = unsafe { PL_get_nil(cursor.term) } != 0;
+        self.cursor = None;
+
+        match is_nil {
+            true => None,
+            false => Some(Err(PartialListError)),
+        }
+    }
+}
+
+/// Error returned by [ListIter] for a list that does not end in `[]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialListError;
+
+impl fmt::Display for PartialListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "list does not end in []")
+    }
 }
 
+impl std::error::Error for PartialListError {}
+
 impl<'a> PartialEq for Term<'a> {
     fn eq(&self, other: &Term) -> bool {
         self.assert_term_handling_possible();
@@ -560,6 +895,34 @@ impl<'a> TermOrigin<'a> {
     }
 }
 
+/// Options for resolving a file path with [Term::get_path_with_options].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PathOptions {
+    /// Resolve path aliases like `library(lists)` using `file_search_path/2`.
+    pub search: bool,
+    /// Make the path absolute.
+    pub absolute: bool,
+    /// Require the file to exist.
+    pub exists: bool,
+}
+
+impl PathOptions {
+    fn flags(&self) -> u32 {
+        let mut flags = PL_FILE_OSPATH | REP_UTF8;
+        if self.search {
+            flags |= PL_FILE_SEARCH;
+        }
+        if self.absolute {
+            flags |= PL_FILE_ABSOLUTE;
+        }
+        if self.exists {
+            flags |= PL_FILE_EXIST;
+        }
+
+        flags
+    }
+}
+
 /// Trait for term unification.
 ///
 /// # Safety
@@ -609,6 +972,16 @@ pub unsafe trait TermGetable: Sized {
 
     /// Get the name of this data type for use in exception reporting.
     fn name() -> &'static str;
+
+    /// Check whether the term holds the kind of data this type is retrieved from, regardless of whether its value fits.
+    ///
+    /// This is used by [Term::get_checked] to tell a value which is
+    /// out of range for this type apart from one of the wrong
+    /// type. The default returns false, so that every failure is
+    /// reported as a wrong type.
+    fn matches_kind(_term: &Term) -> bool {
+        false
+    }
 }
 
 /// Trait for putting data into a term reference.
@@ -683,7 +1056,7 @@ macro_rules! unifiable {
 ///     (Foo, term) => {
 ///         // Body needs to return an Option indicating success or failure.
 ///         // Failure may also be an exception. The wrapper will check for this.
-///         let num: u64 = attempt_opt(term.get()).unwrap_or(None)?;
+///         let num: u64 = term.get().ok()?;
 ///         Some(Foo { num })
 ///     }
 /// }
@@ -731,6 +1104,28 @@ macro_rules! term_getable {
             }
         }
     };
+
+    (($t:ty, $name: tt, $term_: ident) => $b: block, matches_kind($kind_term: ident) => $k: block) => {
+        // unsafe justification: like above, with the same assert in
+        // front of the kind check.
+        unsafe impl<'a> TermGetable for $t {
+            fn get($term_: &Term) -> Option<Self> {
+                $term_.assert_term_handling_possible();
+
+                $b
+            }
+
+            fn name() -> &'static str {
+                $name
+            }
+
+            fn matches_kind($kind_term: &Term) -> bool {
+                $kind_term.assert_term_handling_possible();
+
+                $k
+            }
+        }
+    };
 }
 
 /// Easily implement [TermPutable].
@@ -899,6 +1294,9 @@ term_getable! {
                 Some(out)
             }
         })}
+    },
+    matches_kind(term) => {
+        term.is_integer()
     }
 }
 
@@ -926,6 +1324,9 @@ term_getable! {
         else {
             Some(out)
         }
+    },
+    matches_kind(term) => {
+        term.is_integer()
     }
 }
 
@@ -935,6 +1336,80 @@ term_putable! {
     }
 }
 
+// The other integer types are converted through an i64 or a u64,
+// and are only retrieved if the prolog integer fits. Getting an
+// integer that is out of range fails, just like getting a negative
+// integer as a u64 does. Use [Term::get_checked] to tell these
+// cases apart from the term not being an integer at all.
+//
+// There is no conversion for u8, as `&[u8]` and `Vec<u8>` are
+// already converted as strings, which would conflict with the list
+// conversions for element types.
+macro_rules! integer_conversions {
+    ($via:ty; $($t:ty),*) => {
+        $(
+            unifiable! {
+                (self:$t, term) => {
+                    term.unify(*self as $via).is_ok()
+                }
+            }
+
+            term_getable! {
+                ($t, "integer", term) => {
+                    // on an exception, this returns early with the
+                    // exception still pending, for Term::get to report.
+                    let value: $via = term.get().ok()?;
+
+                    value.try_into().ok()
+                },
+                matches_kind(term) => {
+                    term.is_integer()
+                }
+            }
+
+            term_putable! {
+                (self:$t, term) => {
+                    let _ = term.put(&(*self as $via));
+                }
+            }
+        )*
+    };
+}
+
+integer_conversions!(i64; i8, i16, i32, u16, u32, isize);
+integer_conversions!(u64; usize);
+
+// 128 bit integers are converted through their decimal
+// representation when they don't fit in 64 bits.
+macro_rules! wide_integer_conversions {
+    ($($t:ty),*) => {
+        $(
+            unifiable! {
+                (self:$t, term) => {
+                    term.unify(crate::arithmetic::Number::BigInteger(self.to_string())).is_ok()
+                }
+            }
+
+            term_getable! {
+                ($t, "integer", term) => {
+                    use crate::arithmetic::Number;
+                    // like above, an exception is left pending.
+                    match term.get::<Number>().ok()? {
+                        Number::Integer(i) => i.try_into().ok(),
+                        Number::BigInteger(digits) => digits.parse().ok(),
+                        _ => None,
+                    }
+                },
+                matches_kind(term) => {
+                    term.is_integer()
+                }
+            }
+        )*
+    };
+}
+
+wide_integer_conversions!(i128, u128);
+
 unifiable! {
     (self:f64, term) => {
         let result = unsafe { PL_unify_float(term.term, *self) };
@@ -962,6 +1437,82 @@ term_putable! {
     }
 }
 
+// Prolog floats are doubles, so an f32 is widened on the way in,
+// which is always exact. On the way out, only floats that can be
+// narrowed to an f32 without losing precision are retrieved. Any
+// other float fails, rather than being silently rounded. Retrieve an
+// f64 and convert it with `as` if rounding is acceptable.
+unifiable! {
+    (self:f32, term) => {
+        let result = unsafe { PL_unify_float(term.term, *self as f64) };
+
+        result != 0
+    }
+}
+
+term_getable! {
+    (f32, "float", term) => {
+        let mut out = 0.0;
+        let result = unsafe { PL_get_float(term.term, &mut out) };
+        if result == 0 {
+            None
+        }
+        else if out.is_nan() {
+            Some(f32::NAN)
+        }
+        else if (out as f32) as f64 == out {
+            Some(out as f32)
+        }
+        else {
+            None
+        }
+    },
+    matches_kind(term) => {
+        term.is_float()
+    }
+}
+
+term_putable! {
+    (self:f32, term) => {
+        unsafe { PL_put_float(term.term, *self as f64) };
+    }
+}
+
+// Durations are represented as a float of the total amount of
+// seconds, which is what predicates like sleep/1 and
+// call_with_time_limit/2 expect.
+unifiable! {
+    (self:Duration, term) => {
+        let result = unsafe { PL_unify_float(term.term, self.as_secs_f64()) };
+
+        result != 0
+    }
+}
+
+term_getable! {
+    (Duration, "duration", term) => {
+        let mut out = 0.0;
+        let result = unsafe { PL_get_float(term.term, &mut out) };
+        if result == 0 {
+            None
+        }
+        else {
+            // this rejects negative, infinite and NaN values, as well
+            // as values too large for a Duration.
+            Duration::try_from_secs_f64(out).ok()
+        }
+    },
+    matches_kind(term) => {
+        term.is_integer() || term.is_float()
+    }
+}
+
+term_putable! {
+    (self:Duration, term) => {
+        unsafe { PL_put_float(term.term, self.as_secs_f64()) };
+    }
+}
+
 unifiable! {
     (self:&str, term) => {
         let result = unsafe { PL_unify_chars(
@@ -1287,6 +1838,194 @@ mod tests {
         assert_eq!(42, term1.get::<u64>().unwrap());
     }
 
+    #[test]
+    fn convert_all_integer_types() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        term.unify(-12_i8)?;
+        assert_eq!(-12, term.get::<i8>()?);
+        assert_eq!(-12, term.get::<i32>()?);
+        assert_eq!(-12, term.get::<isize>()?);
+        assert!(term.get::<u16>().unwrap_err().is_failure());
+        assert!(term.get::<usize>().unwrap_err().is_failure());
+
+        let term = context.new_term_ref();
+        term.unify(70000_u32)?;
+        assert_eq!(70000, term.get::<u32>()?);
+        assert_eq!(70000, term.get::<usize>()?);
+        assert!(term.get::<u16>().unwrap_err().is_failure());
+        assert!(term.get::<i16>().unwrap_err().is_failure());
+
+        let term = context.new_term_ref();
+        term.put_val(255_u16)?;
+        assert_eq!(255, term.get::<u16>()?);
+        assert!(term.get::<i8>().unwrap_err().is_failure());
+
+        let term = context.new_term_ref();
+        term.unify(u128::MAX)?;
+        assert_eq!(u128::MAX, term.get::<u128>()?);
+        assert!(term.get::<i128>().unwrap_err().is_failure());
+        assert!(term.get::<u64>().unwrap_err().is_failure());
+
+        let term = context.new_term_ref();
+        term.unify(i128::MIN)?;
+        assert_eq!(i128::MIN, term.get::<i128>()?);
+        let term = context.new_term_ref();
+        term.unify(-5_i128)?;
+        assert_eq!(-5, term.get::<i64>()?);
+        assert!(term.get::<u128>().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_checked_reports_reason() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let negative = term! {context: -1}?;
+        assert_eq!(Ok(-1), negative.get_checked::<i64>());
+        assert_eq!(
+            Err(GetError::OutOfRange {
+                type_name: "integer"
+            }),
+            negative.get_checked::<u64>()
+        );
+        assert!(!context.has_exception());
+
+        let precise = context.new_term_ref();
+        precise.unify(0.1_f64)?;
+        assert_eq!(
+            Err(GetError::OutOfRange { type_name: "float" }),
+            precise.get_checked::<f32>()
+        );
+
+        let atom = term! {context: foo}?;
+        assert_eq!(
+            Err(GetError::WrongType {
+                type_name: "integer"
+            }),
+            atom.get_checked::<u16>()
+        );
+
+        assert_eq!(
+            Err(GetError::OutOfRange {
+                type_name: "duration"
+            }),
+            negative.get_checked::<Duration>()
+        );
+
+        // the kind is decided by the type, not by its name
+        struct Digit(u64);
+        term_getable! {
+            (Digit, "integer", term) => {
+                let n: u64 = term.get().ok()?;
+                if n < 10 {
+                    Some(Digit(n))
+                } else {
+                    None
+                }
+            }
+        }
+        let large = term! {context: 42}?;
+        assert_eq!(
+            Err(GetError::WrongType {
+                type_name: "integer"
+            }),
+            large.get_checked::<Digit>().map(|digit| digit.0)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn unify_and_get_f32s() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term1 = context.new_term_ref();
+        term1.unify(0.1_f32)?;
+        assert_eq!(0.1_f32 as f64, term1.get::<f64>()?);
+        assert_eq!(0.1_f32, term1.get::<f32>()?);
+
+        let term2 = context.new_term_ref();
+        term2.unify(0.1_f64)?;
+        assert!(term2.get::<f32>().unwrap_err().is_failure());
+
+        let term3 = term! {context: 1.5}?;
+        assert_eq!(1.5_f32, term3.get::<f32>()?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unify_and_get_durations() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term1 = context.new_term_ref();
+        term1.unify(Duration::from_millis(1500))?;
+        assert_eq!(1.5, term1.get::<f64>()?);
+        assert_eq!(Duration::from_millis(1500), term1.get::<Duration>()?);
+
+        let term2 = term! {context: 3}?;
+        assert_eq!(Duration::from_secs(3), term2.get::<Duration>()?);
+
+        let term3 = context.new_term_ref();
+        term3.unify(-1.0_f64)?;
+        assert!(term3.get::<Duration>().unwrap_err().is_failure());
+        term3.put(&1.0e300_f64)?;
+        assert!(term3.get::<Duration>().unwrap_err().is_failure());
+
+        let term4 = term! {context: foo}?;
+        assert!(term4.get::<Duration>().unwrap_err().is_failure());
+
+        Ok(())
+    }
+
+    #[test]
+    fn get_paths() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: "some/relative/file.txt"}?;
+        assert_eq!(PathBuf::from("some/relative/file.txt"), term.get_path()?);
+
+        let absolute = term.get_path_with_options(PathOptions {
+            absolute: true,
+            ..Default::default()
+        })?;
+        assert!(absolute.is_absolute());
+        assert!(absolute.ends_with("some/relative/file.txt"));
+
+        let alias = term! {context: library(lists)}?;
+        let lists = alias.get_path_with_options(PathOptions {
+            search: true,
+            ..Default::default()
+        })?;
+        assert!(lists.is_absolute());
+        assert_eq!(Some("lists".as_ref()), lists.file_stem());
+
+        let missing = term! {context: "this/file/does/not/exist.pl"}?;
+        assert!(missing
+            .get_path_with_options(PathOptions {
+                exists: true,
+                ..Default::default()
+            })
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
+
     #[test]
     fn unify_and_get_string_refs() {
         let engine = Engine::new();
@@ -1299,6 +2038,20 @@ mod tests {
         term1
             .get_str(|s| assert_eq!("hello there", s.unwrap()))
             .unwrap();
+
+        let term2 = term! {context: "caf\u{e9} \u{1f980}"}.unwrap();
+        let mut suffix = String::from("!");
+        // consuming suffix makes this closure FnOnce
+        let exclaimed = term2
+            .get_str(move |s| {
+                suffix.insert_str(0, s.unwrap());
+                suffix
+            })
+            .unwrap();
+        assert_eq!("caf\u{e9} \u{1f980}!", exclaimed);
+
+        let term3 = term! {context: hello}.unwrap();
+        assert!(term3.get_str(|s| s.is_none()).unwrap());
     }
 
     #[test]
@@ -1508,6 +2261,22 @@ mod tests {
         context.with_exception(|e| e.unwrap().unify(&expected).unwrap());
     }
 
+    #[test]
+    fn get_arg_fails_out_of_range_and_on_non_compounds() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: foo(42, bar)}.unwrap();
+        assert_eq!(42, term.get_arg::<u64>(1).unwrap());
+        assert!(term.get_arg::<u64>(2).unwrap_err().is_failure());
+        assert!(term.get_arg::<u64>(3).unwrap_err().is_failure());
+
+        let atom = term! {context: foo}.unwrap();
+        assert!(atom.get_arg::<u64>(1).unwrap_err().is_failure());
+        assert!(!context.has_exception());
+    }
+
     #[test]
     fn get_arg_ex_works_with_expected_type_and_arity() {
         let engine = Engine::new();
@@ -1520,4 +2289,186 @@ mod tests {
         assert_eq!(42, result.unwrap());
         assert!(!context.has_exception());
     }
+
+    #[test]
+    fn would_unify_leaves_terms_unbound() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [x, y] = context.new_term_refs();
+        let term1 = term! {context: foo(#&x, bar)}.unwrap();
+        let term2 = term! {context: foo(42, #&y)}.unwrap();
+        assert!(term1.would_unify(&term2));
+        assert!(x.is_var());
+        assert!(y.is_var());
+
+        let term3 = term! {context: foo(42, baz)}.unwrap();
+        assert!(!term1.would_unify(&term3));
+        assert!(x.is_var());
+
+        term1.unify(&term2).unwrap();
+        assert_eq!(42, x.get::<u64>().unwrap());
+    }
+
+    #[test]
+    fn unify_list_cells_one_by_one() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = context.new_term_ref();
+        let (head, tail) = list.unify_list_cell().unwrap();
+        head.unify(Atom::new("first")).unwrap();
+        let (head, tail) = tail.unify_list_cell().unwrap();
+        head.unify(Atom::new("second")).unwrap();
+        tail.unify(Nil).unwrap();
+        assert_eq!(2, list.list_iter().count());
+
+        // an existing list is destructured instead
+        let (head, tail) = list.unify_list_cell().unwrap();
+        assert_eq!(Atom::new("first"), head.get::<Atom>().unwrap());
+        assert_eq!(1, tail.list_iter().count());
+
+        let empty = term! {context: #Nil}.unwrap();
+        assert!(empty.unify_list_cell().unwrap_err().is_failure());
+    }
+
+    #[test]
+    fn iterate_lazily_over_lists() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let list = term! {context: [a, b, c]}.unwrap();
+        let elements: Vec<Atom> = list
+            .list_iter()
+            .map(|e| e.unwrap().get::<Atom>().unwrap())
+            .collect();
+        assert_eq!(
+            vec![Atom::new("a"), Atom::new("b"), Atom::new("c")],
+            elements
+        );
+        assert_eq!(3, list.list_iter().count());
+
+        let empty = term! {context: #Nil}.unwrap();
+        assert_eq!(0, empty.list_iter().count());
+
+        let (partial, _) = context.new_difflist();
+        let elements: Vec<_> = partial.list_iter().collect();
+        assert_eq!(1, elements.len());
+        assert_eq!(Err(PartialListError), elements[0]);
+
+        let (partial, tail) = context.new_difflist();
+        context.difflist_extend(&tail, [1_u64, 2_u64]).unwrap();
+        let mut iter = partial.list_iter();
+        assert_eq!(1, iter.next().unwrap().unwrap().get::<u64>().unwrap());
+        assert_eq!(2, iter.next().unwrap().unwrap().get::<u64>().unwrap());
+        assert_eq!(Some(Err(PartialListError)), iter.next());
+        assert_eq!(None, iter.next());
+    }
+
+    #[test]
+    fn equality_variance_and_unifiability() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let [x, y] = context.new_term_refs();
+        assert!(x.structurally_equal(&x));
+        assert!(!x.structurally_equal(&y));
+        assert!(x.is_variant(&y));
+        assert!(x.would_unify(&y));
+
+        let term1 = term! {context: f(#&x, #&y)}.unwrap();
+        let term2 = term! {context: f(#&y, #&x)}.unwrap();
+        let term3 = term! {context: f(#&x, #&x)}.unwrap();
+        assert!(!term1.structurally_equal(&term2));
+        assert!(term1.is_variant(&term2));
+        assert!(!term1.is_variant(&term3));
+        assert!(term1.would_unify(&term3));
+
+        let term4 = term! {context: f(a, b)}.unwrap();
+        let term5 = term! {context: f(a, b)}.unwrap();
+        assert!(term4.structurally_equal(&term5));
+        assert!(term4.is_variant(&term5));
+        assert!(x.is_var());
+        assert!(y.is_var());
+    }
+
+    #[test]
+    fn derived_term_conversions() -> PrologResult<()> {
+        use crate::{TermGetable, Unifiable};
+
+        #[derive(TermGetable, Unifiable, Debug, PartialEq)]
+        struct MyPoint {
+            x: f64,
+            y: f64,
+        }
+
+        #[derive(TermGetable, Unifiable, Debug, PartialEq)]
+        #[functor("edge")]
+        struct Connection {
+            #[arg(2)]
+            to: Atom,
+            #[arg(1)]
+            from: Atom,
+        }
+
+        #[derive(TermGetable, Unifiable, Debug, PartialEq)]
+        struct Wrapped(u64, String);
+
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = term! {context: my_point(1.5, 2.0)}?;
+        assert_eq!(MyPoint { x: 1.5, y: 2.0 }, term.get::<MyPoint>()?);
+        let term = context.new_term_ref();
+        term.unify(MyPoint { x: 3.0, y: 4.0 })?;
+        assert_eq!("my_point(3.0,4.0)", context.string_from_term(&term)?);
+
+        let term = term! {context: edge(a, b)}?;
+        let connection = Connection {
+            from: Atom::new("a"),
+            to: Atom::new("b"),
+        };
+        assert_eq!(connection, term.get::<Connection>()?);
+        assert!(term.unify(&connection).is_ok());
+        assert!(term
+            .unify(Connection {
+                from: Atom::new("b"),
+                to: Atom::new("a"),
+            })
+            .is_err());
+
+        let term = term! {context: wrapped(42, "hello")}?;
+        assert_eq!(Wrapped(42, "hello".to_string()), term.get::<Wrapped>()?);
+
+        let term = term! {context: my_point(1.5)}?;
+        assert!(term.get::<MyPoint>().unwrap_err().is_failure());
+        let term = term! {context: my_point(a, b)}?;
+        assert!(term.get::<MyPoint>().unwrap_err().is_failure());
+
+        // an exception raised while getting a field is not turned into a failure
+        #[derive(Debug, PartialEq)]
+        struct Raising;
+        term_getable! {
+            (Raising, "raising", term) => {
+                let context = unsafe { unmanaged_engine_context() };
+                let error = term! {context: error(oops, _)}.ok()?;
+                let _ = context.raise_exception::<()>(&error);
+
+                None
+            }
+        }
+        #[derive(TermGetable, Debug, PartialEq)]
+        struct HoldsRaising(u64, Raising);
+
+        let term = term! {context: holds_raising(1, 2)}?;
+        assert!(term.get::<HoldsRaising>().unwrap_err().is_exception());
+        context.clear_exception();
+
+        Ok(())
+    }
 }
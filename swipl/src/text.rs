@@ -1,8 +1,16 @@
 //! Support for easy text extraction from prolog.
+use crate::atom::Atom;
+use crate::engine::is_engine_active;
 use crate::fli;
+use crate::functor::Functor;
+use crate::options::OptionList;
 use crate::term::*;
-use crate::term_getable;
+use crate::{context::*, result::*};
+use crate::{functor, pred, term_getable};
+#[cfg(feature = "utf16")]
+use swipl_macros::term;
 
+use std::fmt;
 use std::os::raw::c_char;
 
 /// A wrapper around an owned string for which [TermGetable](crate::term::TermGetable)
@@ -52,3 +60,425 @@ term_getable! {
         }
     }
 }
+
+impl<'a> Term<'a> {
+    /// Render this term as text, the way `write/1` or `writeq/1` would print it.
+    ///
+    /// If `quoted` is true, atoms and strings are quoted where this
+    /// is needed to read them back, as with `writeq/1`. This does not
+    /// use `portray/1` hooks or operators that are local to a
+    /// module. For full control over the output, use
+    /// [Context::write_term_to_string].
+    ///
+    /// If the text contains code points that can't be part of a rust
+    /// string, such as lone surrogates, an
+    /// `error(representation_error(utf8), _)` is raised. Use
+    /// [write_to_string_lossy](Term::write_to_string_lossy) to
+    /// replace these instead.
+    pub fn write_to_string(&self, quoted: bool) -> PrologResult<String> {
+        match String::from_utf8(self.write_to_bytes(quoted)?) {
+            Ok(text) => Ok(text),
+            Err(_) => unsafe { unmanaged_engine_context() }.raise_representation_error("utf8"),
+        }
+    }
+
+    /// Render this term as text like
+    /// [write_to_string](Term::write_to_string), replacing anything
+    /// that is not valid UTF-8 with U+FFFD.
+    pub fn write_to_string_lossy(&self, quoted: bool) -> PrologResult<String> {
+        Ok(String::from_utf8_lossy(&self.write_to_bytes(quoted)?).into_owned())
+    }
+
+    fn write_to_bytes(&self, quoted: bool) -> PrologResult<Vec<u8>> {
+        self.assert_term_handling_possible();
+        let mut len: usize = 0;
+        let mut s: *mut c_char = std::ptr::null_mut();
+        let write_flag = if quoted {
+            fli::CVT_WRITEQ
+        } else {
+            fli::CVT_WRITE
+        };
+        let flags = write_flag | fli::BUF_DISCARDABLE | fli::REP_UTF8;
+        let result = unsafe { fli::PL_get_nchars(self.term_ptr(), &mut len, &mut s, flags) };
+
+        if unsafe { fli::pl_default_exception() != 0 } {
+            return Err(PrologError::Exception);
+        }
+        if result == 0 {
+            return Err(PrologError::Failure);
+        }
+
+        let slice = unsafe { std::slice::from_raw_parts(s as *const u8, len) };
+
+        Ok(slice.to_vec())
+    }
+}
+
+/// Terms are displayed the way `writeq/1` would print them.
+///
+/// This is handy for logging and error messages. If the term does
+/// not belong to the engine that is active on this thread, or it
+/// can't be written, a placeholder is written instead, so that
+/// formatting never panics.
+impl<'a> fmt::Display for Term<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !is_engine_active(self.origin_engine_ptr()) {
+            return f.write_str("<term: engine not active>");
+        }
+
+        match self.write_to_string_lossy(true) {
+            Ok(text) => f.write_str(&text),
+            Err(_) => f.write_str("<term: could not be written>"),
+        }
+    }
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Render a term as text using `write_term/2` with the given options.
+    ///
+    /// This accepts all options `write_term/2` accepts, such as
+    /// `quoted`, `max_depth` and `portray`. Unlike
+    /// [Term::write_to_string], it runs prolog code, so `portray/1`
+    /// hooks are used when asked for.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let term = term! {context: [1, 2, 3, 4, 5]}?;
+    /// let options = OptionList::new().option("max_depth", 3_u64);
+    /// assert_eq!("[1,2|...]", context.write_term_to_string(&term, &options)?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn write_term_to_string(&self, term: &Term, options: &OptionList) -> PrologResult<String> {
+        let frame = self.open_frame();
+        let [output, goal, string] = frame.new_term_refs();
+        output.unify(functor!("string/1"))?;
+        output.unify_arg(1, &string)?;
+        goal.unify(Functor::new("write_term", 2))?;
+        goal.unify_arg(1, term)?;
+        goal.unify_arg(2, options)?;
+
+        frame.call_once(pred!(with_output_to / 2), [&output, &goal])?;
+        let result = string.get::<String>()?;
+        frame.discard();
+
+        Ok(result)
+    }
+}
+
+/// What to do when reading a term runs into a syntax error.
+///
+/// This corresponds to the `syntax_errors` option of `read_term/2`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SyntaxErrors {
+    /// Raise a syntax error exception. This is the default.
+    #[default]
+    Error,
+    /// Print the syntax error and fail.
+    Fail,
+    /// Fail without printing anything.
+    Quiet,
+}
+
+/// Options for reading a term with [Context::read_term_from_string].
+///
+/// Variable names and singletons are always collected, so there
+/// are no options for those.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ReadTermOptions {
+    module: Option<String>,
+    syntax_errors: SyntaxErrors,
+}
+
+impl ReadTermOptions {
+    /// Create the default read options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the term using the operators and flags of the given module.
+    pub fn module(mut self, module: &str) -> Self {
+        self.module = Some(module.to_string());
+
+        self
+    }
+
+    /// Set what happens on a syntax error.
+    pub fn syntax_errors(mut self, syntax_errors: SyntaxErrors) -> Self {
+        self.syntax_errors = syntax_errors;
+
+        self
+    }
+
+    fn build<'b, T: QueryableContextType>(
+        &self,
+        context: &'b Context<T>,
+        variable_names: &Term,
+        singletons: &Term,
+    ) -> PrologResult<Term<'b>> {
+        let syntax_errors = match self.syntax_errors {
+            SyntaxErrors::Error => "error",
+            SyntaxErrors::Fail => "fail",
+            SyntaxErrors::Quiet => "quiet",
+        };
+        let mut options = OptionList::new()
+            .option("variable_names", variable_names)
+            .option("singletons", singletons)
+            .option("syntax_errors", Atom::new(syntax_errors));
+        if let Some(module) = &self.module {
+            options.add_option("module", Atom::new(module));
+        }
+
+        let result = context.new_term_ref();
+        result.unify(&options)?;
+
+        Ok(result)
+    }
+}
+
+/// A term read by [Context::read_term_from_string], along with its variables.
+#[derive(Debug)]
+pub struct ReadTerm<'a> {
+    /// The term that was read.
+    pub term: Term<'a>,
+    /// The named variables in the term, in order of appearance. Anonymous variables (`_`) are not included.
+    pub variable_names: Vec<(String, Term<'a>)>,
+    /// The named variables that appear only once in the term.
+    pub singletons: Vec<(String, Term<'a>)>,
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Turn the given string into a prolog term, using the given read options.
+    ///
+    /// Unlike [term_from_string](Context::term_from_string), this
+    /// also returns the names of the variables in the term, as
+    /// `read_term/2` would with its `variable_names` and
+    /// `singletons` options. Reading is done with `term_string/3`.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let read = context.read_term_from_string("foo(X, Y, X)", &ReadTermOptions::new())?;
+    /// let names: Vec<_> = read.variable_names.iter().map(|(n, _)| n.as_str()).collect();
+    /// assert_eq!(vec!["X", "Y"], names);
+    ///
+    /// read.variable_names[0].1.unify(42_u64)?;
+    /// assert_eq!(42, read.term.get_arg::<u64>(3)?);
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn read_term_from_string(
+        &self,
+        s: &str,
+        options: &ReadTermOptions,
+    ) -> PrologResult<ReadTerm> {
+        let [term, variable_names, singletons] = self.new_term_refs();
+
+        let frame = self.open_frame();
+        let text = frame.new_term_ref();
+        text.unify(s)?;
+        let options = options.build(&frame, &variable_names, &singletons)?;
+        frame.call_once(pred!(term_string / 3), [&term, &text, &options])?;
+        frame.close();
+
+        Ok(ReadTerm {
+            term,
+            variable_names: self.name_value_pairs(&variable_names)?,
+            singletons: self.name_value_pairs(&singletons)?,
+        })
+    }
+
+    /// Turn a list of `Name = Var` pairs into a vector.
+    fn name_value_pairs(&self, list: &Term) -> PrologResult<Vec<(String, Term)>> {
+        let mut result = Vec::new();
+        for pair in self.term_list_iter(list) {
+            let name: Atom = pair.get_arg(1)?;
+            let var = self.new_term_ref();
+            pair.unify_arg(2, &var)?;
+            result.push((name.name(), var));
+        }
+
+        Ok(result)
+    }
+}
+
+#[cfg(feature = "utf16")]
+impl<'a> Term<'a> {
+    /// Retrieve the text of an atom or string as UTF-16 code units.
+    ///
+    /// This will fail if the term is not an atom or a string. If the
+    /// text contains code points that cannot be represented in
+    /// UTF-16 (such as lone surrogates), an
+    /// `error(representation_error(utf16), _)` is raised.
+    pub fn get_utf16(&self) -> PrologResult<Vec<u16>> {
+        self.assert_term_handling_possible();
+        let mut len: usize = 0;
+        let mut s: *mut c_char = std::ptr::null_mut();
+        let flags = fli::CVT_ATOM | fli::CVT_STRING | fli::BUF_DISCARDABLE | fli::REP_UTF8;
+        let result = unsafe { fli::PL_get_nchars(self.term_ptr(), &mut len, &mut s, flags) };
+
+        if unsafe { fli::pl_default_exception() != 0 } {
+            return Err(PrologError::Exception);
+        }
+        if result == 0 {
+            return Err(PrologError::Failure);
+        }
+
+        let slice = unsafe { std::slice::from_raw_parts(s as *const u8, len) };
+        match std::str::from_utf8(slice) {
+            Ok(text) => Ok(text.encode_utf16().collect()),
+            Err(_) => raise_utf16_representation_error(),
+        }
+    }
+
+    /// Unify this term with a string made out of the given UTF-16 code units.
+    ///
+    /// If the code units are not valid UTF-16, for example because
+    /// they contain a lone surrogate, an
+    /// `error(representation_error(utf16), _)` is raised instead.
+    pub fn unify_utf16(&self, units: &[u16]) -> PrologResult<()> {
+        match String::from_utf16(units) {
+            Ok(s) => self.unify(s.as_str()),
+            Err(_) => raise_utf16_representation_error(),
+        }
+    }
+}
+
+#[cfg(feature = "utf16")]
+fn raise_utf16_representation_error<R>() -> PrologResult<R> {
+    let context = unsafe { unmanaged_engine_context() };
+    let reset_term = context.new_term_ref();
+    let exception_term = term! {context: error(representation_error(utf16), _)};
+    let result = exception_term.and_then(|t| context.raise_exception(&t));
+    unsafe { reset_term.reset() };
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn write_terms_to_strings() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.term_from_string("foo('Bar', \"baz\", [1, 2])")?;
+        assert_eq!("foo(Bar,baz,[1,2])", term.write_to_string(false)?);
+        assert_eq!("foo('Bar',\"baz\",[1,2])", term.write_to_string(true)?);
+        assert_eq!("foo('Bar',\"baz\",[1,2])", format!("{}", term));
+
+        let surrogate = context.new_term_ref();
+        context.call_once(
+            pred!(atom_codes / 2),
+            [&surrogate, &term! {context: [0xd800]}?],
+        )?;
+        assert!(surrogate.write_to_string(false).unwrap_err().is_exception());
+        context.clear_exception();
+        assert!(surrogate.write_to_string_lossy(false)?.contains('\u{fffd}'));
+        assert!(format!("{}", surrogate).contains('\u{fffd}'));
+
+        let options = OptionList::new()
+            .option("quoted", atom!("true"))
+            .option("spacing", atom!("next_argument"));
+        assert_eq!(
+            "foo('Bar', \"baz\", [1, 2])",
+            context.write_term_to_string(&term, &options)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_terms_with_options() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let read = context.read_term_from_string("foo(X, _, Y, X)", &ReadTermOptions::new())?;
+        let names: Vec<_> = read
+            .variable_names
+            .iter()
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(vec!["X", "Y"], names);
+        let singletons: Vec<_> = read.singletons.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(vec!["Y"], singletons);
+
+        assert!(context
+            .read_term_from_string("foo(", &ReadTermOptions::new())
+            .unwrap_err()
+            .is_exception());
+        context.clear_exception();
+        let quiet = ReadTermOptions::new().syntax_errors(SyntaxErrors::Quiet);
+        assert!(context
+            .read_term_from_string("foo(", &quiet)
+            .unwrap_err()
+            .is_failure());
+
+        let op = context.term_from_string("op(700, xfx, rust_read:(===>))")?;
+        context.call_once(pred!(call / 1), [&op])?;
+        let in_module = ReadTermOptions::new().module("rust_read");
+        let read = context.read_term_from_string("a ===> b", &in_module)?;
+        assert_eq!(functor!("===>/2"), read.term.get::<Functor>()?);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "utf16")]
+    #[test]
+    fn utf16_roundtrip() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let units: Vec<u16> = "h\u{e9}llo \u{1f600}".encode_utf16().collect();
+        let term = context.new_term_ref();
+        term.unify_utf16(&units)?;
+        assert_eq!("h\u{e9}llo \u{1f600}", term.get::<String>()?);
+        assert_eq!(units, term.get_utf16()?);
+
+        let atom = term! {context: foo}?;
+        assert_eq!(vec![0x66, 0x6f, 0x6f], atom.get_utf16()?);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "utf16")]
+    #[test]
+    fn utf16_lone_surrogate_is_error() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        let result = term.unify_utf16(&[0x61, 0xd800, 0x62]);
+        assert!(result.unwrap_err().is_exception());
+        assert!(context.has_exception());
+        context.clear_exception();
+        assert!(term.is_var());
+    }
+
+    #[cfg(feature = "utf16")]
+    #[test]
+    fn utf16_from_non_text_fails() {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context.new_term_ref();
+        term.unify(42_u64).unwrap();
+        assert!(term.get_utf16().unwrap_err().is_failure());
+    }
+}
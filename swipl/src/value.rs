@@ -0,0 +1,218 @@
+//! Owned representations of prolog terms.
+//!
+//! A [Term] is only a reference into the stack of a single engine,
+//! which makes it impossible to hold on to it past the lifetime of
+//! its context, or to send it to another thread. A [PrologValue] is
+//! a plain rust value holding the entire structure of a term, which
+//! can be turned back into a term on any engine.
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use crate::arithmetic::*;
+use crate::atom::*;
+use crate::context::*;
+use crate::functor::*;
+use crate::pred;
+use crate::result::*;
+use crate::term::*;
+
+/// An owned copy of a prolog term.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrologValue {
+    /// A variable. Variables are numbered in the order of their
+    /// first occurrence in the term, so that multiple occurrences of
+    /// the same variable share the same number.
+    Variable(usize),
+    /// An atom.
+    Atom(Atom),
+    /// The empty list.
+    Nil,
+    /// A number.
+    Number(Number),
+    /// A string.
+    String(String),
+    /// A compound term, with its name and its arguments. Non-empty
+    /// lists are compound terms with the name `'[|]'`.
+    Compound(Atom, Vec<PrologValue>),
+}
+
+impl<'a, T: QueryableContextType> Context<'a, T> {
+    /// Copy the given term into a [PrologValue].
+    ///
+    /// This fails for terms containing dicts, or blobs which are not
+    /// atoms. As the term is copied recursively, this should not be
+    /// used with cyclic terms.
+    pub fn to_owned_value(&self, term: &Term) -> PrologResult<PrologValue> {
+        let frame = self.open_frame();
+        let variables_list = frame.new_term_ref();
+        frame.call_once(pred!(term_variables / 2), [term, &variables_list])?;
+        let variables: Vec<Term> = frame.term_list_iter(&variables_list).collect();
+
+        let result = owned_value(&frame, term, &variables);
+        frame.discard();
+
+        result
+    }
+
+    /// Build a term out of the given [PrologValue].
+    ///
+    /// This is the inverse of
+    /// [to_owned_value](Context::to_owned_value). Variables in the
+    /// value become fresh variables in the new term, with variables
+    /// sharing the same number becoming the same variable.
+    ///
+    /// Example:
+    /// ```
+    /// # use swipl::prelude::*;
+    /// # fn main() -> PrologResult<()> {
+    /// #  let engine = Engine::new();
+    /// #  let activation = engine.activate();
+    /// #  let context: Context<_> = activation.into();
+    /// let term = context.term_from_string("foo(X, bar, X)")?;
+    /// let value = context.to_owned_value(&term)?;
+    ///
+    /// std::thread::spawn(move || {
+    ///     let engine = Engine::new();
+    ///     let activation = engine.activate();
+    ///     let context: Context<_> = activation.into();
+    ///
+    ///     let term = context.value_to_term(&value).unwrap();
+    ///     assert_eq!(atom!("bar"), term.get_arg::<Atom>(2).unwrap());
+    ///     term.unify_arg(1, 42_u64).unwrap();
+    ///     assert_eq!(42, term.get_arg::<u64>(3).unwrap());
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// #  Ok(())
+    /// # }
+    /// ```
+    pub fn value_to_term(&self, value: &PrologValue) -> PrologResult<Term> {
+        let term = self.new_term_ref();
+        let frame = self.open_frame();
+        let mut variables = HashMap::new();
+        build_term(&frame, &term, value, &mut variables)?;
+        frame.close();
+
+        Ok(term)
+    }
+}
+
+fn owned_value<C: QueryableContextType>(
+    context: &Context<C>,
+    term: &Term,
+    variables: &[Term],
+) -> PrologResult<PrologValue> {
+    match term.term_type() {
+        TermType::Variable => {
+            let index = variables
+                .iter()
+                .position(|v| v == term)
+                .expect("variable should have been found by term_variables/2");
+
+            Ok(PrologValue::Variable(index))
+        }
+        TermType::Atom => Ok(PrologValue::Atom(term.get()?)),
+        TermType::Nil => Ok(PrologValue::Nil),
+        TermType::Integer | TermType::Rational | TermType::Float => {
+            Ok(PrologValue::Number(term.get()?))
+        }
+        TermType::String => Ok(PrologValue::String(term.get()?)),
+        TermType::CompoundTerm | TermType::ListPair => {
+            let functor: Functor = term.get()?;
+            let args = context
+                .compound_terms_vec(term)?
+                .iter()
+                .map(|arg| owned_value(context, arg, variables))
+                .collect::<PrologResult<_>>()?;
+
+            Ok(PrologValue::Compound(functor.name(), args))
+        }
+        _ => Err(PrologError::Failure),
+    }
+}
+
+fn build_term<'a, C: QueryableContextType>(
+    context: &'a Context<C>,
+    term: &Term,
+    value: &PrologValue,
+    variables: &mut HashMap<usize, Term<'a>>,
+) -> PrologResult<()> {
+    match value {
+        PrologValue::Variable(index) => match variables.get(index) {
+            Some(variable) => term.unify(variable),
+            None => {
+                let variable = context.new_term_ref();
+                variable.put(term)?;
+                variables.insert(*index, variable);
+
+                Ok(())
+            }
+        },
+        PrologValue::Atom(atom) => term.unify(atom),
+        PrologValue::Nil => term.unify(Nil),
+        PrologValue::Number(Number::Integer(i)) => term.unify(*i),
+        PrologValue::Number(Number::Float(f)) => term.unify(*f),
+        PrologValue::Number(Number::BigInteger(digits)) => {
+            term.unify(context.term_from_string(digits)?)
+        }
+        PrologValue::Number(Number::Rational(numerator, denominator)) => {
+            term.unify(context.term_from_string(&format!("{}r{}", numerator, denominator))?)
+        }
+        PrologValue::String(s) => term.unify(s.as_str()),
+        PrologValue::Compound(name, args) => {
+            let arity = args
+                .len()
+                .try_into()
+                .expect("compound term has too many arguments");
+            term.unify(Functor::new(name, arity))?;
+            for (i, arg) in args.iter().enumerate() {
+                let arg_term = context.new_term_ref();
+                term.unify_arg(i + 1, &arg_term)?;
+                build_term(context, &arg_term, arg, variables)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom;
+    use crate::engine::*;
+
+    #[test]
+    fn roundtrip_owned_value() -> PrologResult<()> {
+        let engine = Engine::new();
+        let activation = engine.activate();
+        let context: Context<_> = activation.into();
+
+        let term = context
+            .term_from_string("foo(X, [1, 2.5|Y], \"bar\", X, [], 1r3, 100000000000000000000)")?;
+        let value = context.to_owned_value(&term)?;
+
+        match &value {
+            PrologValue::Compound(name, args) => {
+                assert_eq!(&atom!("foo"), name);
+                assert_eq!(PrologValue::Variable(0), args[0]);
+                assert_eq!(PrologValue::Variable(0), args[3]);
+                assert_eq!(PrologValue::Nil, args[4]);
+                assert_eq!(PrologValue::String("bar".to_string()), args[2]);
+                assert_eq!(
+                    PrologValue::Number(Number::Rational("1".to_string(), "3".to_string())),
+                    args[5]
+                );
+            }
+            _ => panic!("expected a compound term"),
+        }
+
+        let rebuilt = context.value_to_term(&value)?;
+        context.call_once(pred!(variant / 2), [&term, &rebuilt])?;
+        rebuilt.unify_arg(1, 42_u64)?;
+        assert_eq!(42, rebuilt.get_arg::<u64>(4)?);
+        assert_eq!(value, context.to_owned_value(&term)?);
+
+        Ok(())
+    }
+}